@@ -0,0 +1,373 @@
+use serde_json::{Map, Number, Value};
+
+use crate::{BizObject, ConverterParameter, ConverterReturned, Executor, ExecutorSetting, Instance, NatureError, Protocol, Result, SettingVerifier, verify_setting};
+
+/// The signature of the converters for `Protocol::BuiltIn`
+pub type BuiltInConverter = fn(ConverterParameter) -> ConverterReturned;
+
+/// names can be used as `Executor::url` for `Protocol::BuiltIn`
+pub static BUILT_IN_NAMES: [&str; 6] = ["copy", "projection", "merge_context", "sum", "count", "timer"];
+
+pub fn built_in_converter(name: &str) -> Result<BuiltInConverter> {
+    let rtn: BuiltInConverter = match name {
+        "copy" => copy,
+        "projection" => projection,
+        "merge_context" => merge_context,
+        "sum" => sum,
+        "count" => count,
+        "timer" => timer,
+        _ => return Err(NatureError::VerifyError(format!("unknown built-in executor: {}", name)))
+    };
+    Ok(rtn)
+}
+
+/// verify the settings for the built-in executor
+pub(crate) fn built_in_verifier(name: &str) -> Option<SettingVerifier> {
+    let rtn: SettingVerifier = match name {
+        "copy" => verify_setting::<CopySetting>,
+        "projection" => verify_setting::<ProjectionSetting>,
+        "merge_context" => verify_setting::<MergeContextSetting>,
+        "sum" | "count" => verify_setting::<AggregateSetting>,
+        "timer" => verify_setting::<TimerSetting>,
+        _ => return None
+    };
+    Some(rtn)
+}
+
+impl Executor {
+    /// call the converter of `Protocol::BuiltIn`, `Executor::settings` is used as `ConverterParameter::cfg`
+    pub fn call_built_in(&self, mut para: ConverterParameter) -> Result<ConverterReturned> {
+        if self.protocol != Protocol::BuiltIn {
+            return Err(NatureError::VerifyError(format!("{:?} is not a built-in executor", self.protocol)));
+        }
+        let converter = built_in_converter(&self.url)?;
+        para.cfg = self.settings.clone();
+        Ok(converter(para))
+    }
+}
+
+fn run<F>(para: ConverterParameter, f: F) -> ConverterReturned
+    where F: FnOnce(ConverterParameter) -> Result<ConverterReturned>
+{
+    match f(para) {
+        Ok(rtn) => rtn,
+        Err(e) => e.into()
+    }
+}
+
+fn one_instance(content: String) -> ConverterReturned {
+    let data = BizObject { content, ..Default::default() };
+    ConverterReturned::Instances(vec![Instance { data, ..Default::default() }])
+}
+
+fn content_value(para: &ConverterParameter) -> Result<Value> {
+    para.from.get_content::<Value>()
+}
+
+/// find the value by json pointer, empty `path` means the whole value
+fn pointer<'a>(value: &'a Value, path: &str) -> Result<&'a Value> {
+    match value.pointer(path) {
+        Some(rtn) => Ok(rtn),
+        None => Err(NatureError::LogicalError(format!("can't find [{}] in content", path)))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct CopySetting {
+    /// copy the context too
+    #[serde(default)]
+    pub context: bool,
+}
+
+impl ExecutorSetting for CopySetting {}
+
+/// copy the content of upstream
+fn copy(para: ConverterParameter) -> ConverterReturned {
+    run(para, |para| {
+        let cfg: CopySetting = para.setting()?;
+        let data = BizObject {
+            content: para.from.content.clone(),
+            context: if cfg.context { para.from.context.clone() } else { Default::default() },
+            ..Default::default()
+        };
+        Ok(ConverterReturned::Instances(vec![Instance { data, ..Default::default() }]))
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ProjectionSetting {
+    /// json pointers, the last segment will be used as the field name of the result, e.g. `/order/id` → `id`
+    pub fields: Vec<String>,
+    /// skip the missed fields instead of returning error
+    #[serde(default)]
+    pub ignore_missing: bool,
+}
+
+impl ExecutorSetting for ProjectionSetting {
+    fn verify(&self) -> Result<()> {
+        match self.fields.is_empty() {
+            true => Err(NatureError::VerifyError("fields of projection should not be empty".to_string())),
+            false => Ok(())
+        }
+    }
+}
+
+/// select some fields from the content
+fn projection(para: ConverterParameter) -> ConverterReturned {
+    run(para, |para| {
+        let cfg: ProjectionSetting = para.setting()?;
+        let content = content_value(&para)?;
+        let mut rtn = Map::new();
+        for field in &cfg.fields {
+            let name = field.rsplit('/').next().unwrap_or(field);
+            match pointer(&content, field) {
+                Ok(v) => { rtn.insert(name.to_string(), v.clone()); }
+                Err(_) if cfg.ignore_missing => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(one_instance(Value::Object(rtn).to_string()))
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct MergeContextSetting {
+    /// the context keys to be merged, empty means all
+    #[serde(default)]
+    pub keys: Vec<String>,
+    /// merge into this field of the content, `None` means into the content itself
+    #[serde(default)]
+    pub target: Option<String>,
+}
+
+impl ExecutorSetting for MergeContextSetting {}
+
+/// merge context into content, the context value which is not a json will be treated as a string.
+fn merge_context(para: ConverterParameter) -> ConverterReturned {
+    run(para, |para| {
+        let cfg: MergeContextSetting = para.setting()?;
+        let mut content = match para.from.content.is_empty() {
+            true => Value::Object(Map::new()),
+            false => content_value(&para)?
+        };
+        let mut keys: Vec<&String> = match cfg.keys.is_empty() {
+            true => para.from.context.keys().collect(),
+            false => cfg.keys.iter().collect()
+        };
+        keys.sort();
+        let obj = match &cfg.target {
+            None => content.as_object_mut(),
+            Some(target) => {
+                let root = content.as_object_mut()
+                    .ok_or_else(|| NatureError::LogicalError("content should be a json object".to_string()))?;
+                root.entry(target.to_string()).or_insert_with(|| Value::Object(Map::new())).as_object_mut()
+            }
+        };
+        let obj = obj.ok_or_else(|| NatureError::LogicalError("merge target should be a json object".to_string()))?;
+        for key in keys {
+            let value: Value = match para.from.get_context(key)? {
+                Some(v) => v,
+                None => return Err(NatureError::LogicalError(format!("context [{}] not found", key)))
+            };
+            obj.insert(key.to_string(), value);
+        }
+        Ok(one_instance(content.to_string()))
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct AggregateSetting {
+    /// json pointer to the array in content, empty means the content itself
+    #[serde(default)]
+    pub array: String,
+    /// json pointer to the number in each element, empty means the element itself. only used by `sum`
+    #[serde(default)]
+    pub field: String,
+}
+
+impl ExecutorSetting for AggregateSetting {}
+
+fn array<'a>(content: &'a Value, cfg: &AggregateSetting) -> Result<&'a Vec<Value>> {
+    match pointer(content, &cfg.array)? {
+        Value::Array(rtn) => Ok(rtn),
+        _ => Err(NatureError::LogicalError(format!("[{}] of content should be an array", cfg.array)))
+    }
+}
+
+/// sum the numbers of an array, the result is an integer if all the numbers are integers and it does not overflow
+fn sum(para: ConverterParameter) -> ConverterReturned {
+    run(para, |para| {
+        let cfg: AggregateSetting = para.setting()?;
+        let content = content_value(&para)?;
+        let mut int_sum: i64 = 0;
+        let mut float_sum: f64 = 0.0;
+        let mut is_int = true;
+        for one in array(&content, &cfg)? {
+            let num = match pointer(one, &cfg.field)? {
+                Value::Number(n) => n,
+                other => return Err(NatureError::LogicalError(format!("{} is not a number", other)))
+            };
+            if is_int {
+                match num.as_i64().and_then(|i| int_sum.checked_add(i)) {
+                    Some(sum) => int_sum = sum,
+                    None => is_int = false
+                }
+            }
+            float_sum += num.as_f64().unwrap_or_default();
+        }
+        let rtn = match is_int {
+            true => Value::Number(Number::from(int_sum)),
+            false => Number::from_f64(float_sum).map(Value::Number).unwrap_or(Value::Null)
+        };
+        Ok(one_instance(rtn.to_string()))
+    })
+}
+
+/// count the elements of an array
+fn count(para: ConverterParameter) -> ConverterReturned {
+    run(para, |para| {
+        let cfg: AggregateSetting = para.setting()?;
+        let content = content_value(&para)?;
+        Ok(one_instance(array(&content, &cfg)?.len().to_string()))
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct TimerSetting {
+    /// seconds to delay
+    #[serde(default)]
+    pub seconds: u32,
+    /// read seconds from this context key if it exists, it takes precedence over `seconds`
+    #[serde(default)]
+    pub context: Option<String>,
+}
+
+impl ExecutorSetting for TimerSetting {
+    fn verify(&self) -> Result<()> {
+        match self.seconds == 0 && self.context.is_none() {
+            true => Err(NatureError::VerifyError("seconds of timer should be greater than 0".to_string())),
+            false => Ok(())
+        }
+    }
+}
+
+/// return `ConverterReturned::Delay`, Nature will go on after the seconds.
+fn timer(para: ConverterParameter) -> ConverterReturned {
+    run(para, |para| {
+        let cfg: TimerSetting = para.setting()?;
+        let from_context: Option<u32> = match &cfg.context {
+            Some(key) => para.from.get_context(key)?,
+            None => None
+        };
+        let seconds = from_context.unwrap_or(cfg.seconds);
+        if seconds == 0 {
+            return Err(NatureError::VerifyError("seconds of timer should be greater than 0".to_string()));
+        }
+        Ok(ConverterReturned::Delay(seconds))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn para(content: &str, cfg: &str) -> ConverterParameter {
+        let mut from = Instance::new("a").unwrap();
+        from.content = content.to_string();
+        from.context.insert("shop".to_string(), "s1".to_string());
+        from.context.insert("count".to_string(), "3".to_string());
+        ConverterParameter {
+            from,
+            last_state: None,
+            task_id: "".to_string(),
+            master: None,
+            cfg: cfg.to_string(),
+        }
+    }
+
+    fn content(rtn: ConverterReturned) -> String {
+        match rtn {
+            ConverterReturned::Instances(ins) => ins[0].content.clone(),
+            _ => panic!("should return instances, but get {:?}", rtn)
+        }
+    }
+
+    fn call(name: &str, content: &str, cfg: &str) -> ConverterReturned {
+        built_in_converter(name).unwrap()(para(content, cfg))
+    }
+
+    #[test]
+    fn catalogue_test() {
+        BUILT_IN_NAMES.iter().for_each(|one| assert_eq!(built_in_converter(one).is_ok(), true));
+        assert_eq!(built_in_converter("none").is_err(), true);
+        let exe = Executor { protocol: Protocol::BuiltIn, url: "count".to_string(), settings: r#"{"array":"/a"}"#.to_string() };
+        assert_eq!(content(exe.call_built_in(para(r#"{"a":[1,2]}"#, "")).unwrap()), "2");
+        assert_eq!(Executor::for_local("a:b").call_built_in(para("", "")).is_err(), true);
+    }
+
+    #[test]
+    fn copy_test() {
+        let rtn = call("copy", "hello", "");
+        match rtn {
+            ConverterReturned::Instances(ins) => {
+                assert_eq!(ins[0].content, "hello");
+                assert_eq!(ins[0].context.is_empty(), true);
+            }
+            _ => panic!("should return instances")
+        }
+        match call("copy", "hello", r#"{"context":true}"#) {
+            ConverterReturned::Instances(ins) => assert_eq!(ins[0].context["shop"], "s1"),
+            _ => panic!("should return instances")
+        }
+        match call("copy", "hello", "{bad") {
+            ConverterReturned::LogicalError(msg) => assert_eq!(msg.contains("illegal executor settings"), true),
+            _ => panic!("should return error")
+        }
+    }
+
+    #[test]
+    fn projection_test() {
+        let c = r#"{"order":{"id":5,"price":10},"user":"u1","x":1}"#;
+        assert_eq!(content(call("projection", c, r#"{"fields":["/order/id","/user"]}"#)), r#"{"id":5,"user":"u1"}"#);
+        assert_eq!(content(call("projection", c, r#"{"fields":["/none","/x"],"ignore_missing":true}"#)), r#"{"x":1}"#);
+        assert_eq!(call("projection", c, r#"{"fields":["/none"]}"#), ConverterReturned::LogicalError(r#"LogicalError("can't find [/none] in content")"#.to_string()));
+    }
+
+    #[test]
+    fn merge_context_test() {
+        assert_eq!(content(call("merge_context", r#"{"a":1}"#, "")), r#"{"a":1,"count":3,"shop":"s1"}"#);
+        assert_eq!(content(call("merge_context", "", r#"{"keys":["shop"],"target":"ctx"}"#)), r#"{"ctx":{"shop":"s1"}}"#);
+        assert_eq!(call("merge_context", "[1]", ""), ConverterReturned::LogicalError(r#"LogicalError("merge target should be a json object")"#.to_string()));
+        match call("merge_context", "{}", r#"{"keys":["none"]}"#) {
+            ConverterReturned::LogicalError(_) => (),
+            _ => panic!("should return error")
+        }
+    }
+
+    #[test]
+    fn aggregate_test() {
+        assert_eq!(content(call("sum", "[1,2,3]", "")), "6");
+        assert_eq!(content(call("sum", "[1,2.5]", "")), "3.5");
+        assert_eq!(content(call("sum", "[9223372036854775807,1]", "")), "9.223372036854776e+18");
+        assert_eq!(content(call("sum", "[18446744073709551615,-1]", "")), "1.8446744073709552e+19");
+        assert_eq!(content(call("sum", r#"{"items":[{"n":1},{"n":4}]}"#, r#"{"array":"/items","field":"/n"}"#)), "5");
+        assert_eq!(content(call("count", "[1,2,3]", "")), "3");
+        assert_eq!(content(call("count", "[]", "")), "0");
+        match call("sum", r#"[1,"a"]"#, "") {
+            ConverterReturned::LogicalError(_) => (),
+            _ => panic!("should return error")
+        }
+        match call("count", "{}", "") {
+            ConverterReturned::LogicalError(_) => (),
+            _ => panic!("should return error")
+        }
+    }
+
+    #[test]
+    fn timer_test() {
+        assert_eq!(call("timer", "", r#"{"seconds":10}"#), ConverterReturned::Delay(10));
+        assert_eq!(call("timer", "", r#"{"seconds":10,"context":"count"}"#), ConverterReturned::Delay(3));
+        assert_eq!(call("timer", "", r#"{"seconds":10,"context":"none"}"#), ConverterReturned::Delay(10));
+        assert_eq!(call("timer", "", ""), ConverterReturned::LogicalError(r#"VerifyError("seconds of timer should be greater than 0")"#.to_string()));
+    }
+}
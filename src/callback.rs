@@ -1,7 +1,182 @@
-use crate::ConverterReturned;
+use std::time::Duration;
+
+use actix::clock::delay_for;
+use chrono::prelude::*;
+use hmac::{Hmac, Mac, NewMac};
+use reqwest::Client;
+use reqwest::header::CONTENT_TYPE;
+use sha2::Sha256;
+
+use crate::{ConverterReturned, DetailedError, NatureError, Problem, Result};
+
+/// the header to carry the signature of the payload, its value is `sha256=` followed by the hex of HMAC-SHA256
+pub static SIGNATURE_HEADER: &str = "X-Nature-Signature";
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct DelayedInstances {
     pub task_id: String,
     pub result: ConverterReturned,
 }
+
+/// the time in milliseconds that `ConverterReturned::Delay(seconds)` promised, begin from now
+pub fn delay_deadline(seconds: u32) -> i64 {
+    Local::now().timestamp_millis() + i64::from(seconds) * 1000
+}
+
+/// HMAC-SHA256 of the payload in hex
+pub fn sign_payload(secret: &[u8], payload: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_varkey(secret).expect("HMAC accepts key of any size");
+    mac.update(payload);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Post `DelayedInstances` back to Nature for the converters which returned `ConverterReturned::Delay`.
+#[derive(Debug, Clone)]
+pub struct CallbackClient {
+    client: Client,
+    url: String,
+    /// retry times after the first failure
+    retry: u32,
+    /// the wait before the first retry, it will be doubled for each retry
+    backoff: Duration,
+    secret: Option<Vec<u8>>,
+}
+
+impl CallbackClient {
+    pub fn new(url: &str, timeout: Duration) -> Result<Self> {
+        Ok(CallbackClient {
+            client: Client::builder().timeout(timeout).build()?,
+            url: url.to_string(),
+            retry: 3,
+            backoff: Duration::from_millis(500),
+            secret: None,
+        })
+    }
+
+    pub fn retry(mut self, times: u32, backoff: Duration) -> Self {
+        self.retry = times;
+        self.backoff = backoff;
+        self
+    }
+
+    /// sign the payload with HMAC-SHA256, the signature is sent by `SIGNATURE_HEADER`
+    pub fn secret(mut self, secret: &[u8]) -> Self {
+        self.secret = Some(secret.to_vec());
+        self
+    }
+
+    /// Send `delayed` before `deadline` which is the milliseconds got from `delay_deadline`.
+    /// The retryable errors will be retried, but nothing will be sent after the `deadline`,
+    /// because Nature may have redone the task.
+    pub async fn send(&self, delayed: &DelayedInstances, deadline: i64) -> std::result::Result<(), DetailedError> {
+        let payload = serde_json::to_string(delayed)?;
+        let mut wait = self.backoff;
+        let mut times = 0;
+        loop {
+            check_deadline(&delayed.task_id, deadline)?;
+            let e = match self.post(&payload).await {
+                Ok(_) => return Ok(()),
+                Err(e) => e.with_task(&delayed.task_id)
+            };
+            if !e.is_retryable() || times >= self.retry {
+                warn!("callback failed: {}", e);
+                return Err(e);
+            }
+            let remained = deadline - Local::now().timestamp_millis();
+            if remained <= wait.as_millis() as i64 {
+                warn!("callback failed and no time to retry: {}", e);
+                return Err(e);
+            }
+            times += 1;
+            debug!("callback for task [{}] will retry after {:?}, times: {}", delayed.task_id, wait, times);
+            delay_for(wait).await;
+            wait *= 2;
+        }
+    }
+
+    async fn post(&self, payload: &str) -> std::result::Result<(), DetailedError> {
+        let mut request = self.client.post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(payload.to_string());
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, format!("sha256={}", sign_payload(secret, payload.as_bytes())));
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = response.text().await?;
+        Err(Problem::parse(status.as_u16(), &body))
+    }
+}
+
+fn check_deadline(task_id: &str, deadline: i64) -> Result<()> {
+    let now = Local::now().timestamp_millis();
+    if now > deadline {
+        let msg = format!("callback for task [{}] is {} ms later than the promised delay", task_id, now - deadline);
+        warn!("{}", &msg);
+        return Err(NatureError::LogicalError(msg));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use actix::System;
+
+    use crate::{ErrorCode, ErrorKind};
+    use crate::test_server::{serve, statuses};
+
+    use super::*;
+
+    fn delayed() -> DelayedInstances {
+        DelayedInstances { task_id: "t1".to_string(), result: ConverterReturned::None }
+    }
+
+    fn send(client: &CallbackClient, deadline: i64) -> std::result::Result<(), DetailedError> {
+        let client = client.clone();
+        System::new("test").block_on(async move { client.send(&delayed(), deadline).await })
+    }
+
+    #[test]
+    fn retry_test() {
+        let (url, received) = serve("/callback", statuses(&[500, 503, 200]));
+        let client = CallbackClient::new(&url, Duration::from_secs(3)).unwrap().retry(3, Duration::from_millis(10));
+        assert_eq!(send(&client, delay_deadline(10)), Ok(()));
+        assert_eq!(received.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn no_retry_test() {
+        let (url, received) = serve("/callback", statuses(&[400, 503, 503]));
+        let client = CallbackClient::new(&url, Duration::from_secs(3)).unwrap().retry(1, Duration::from_millis(10));
+        assert_eq!(send(&client, delay_deadline(10)).unwrap_err().code, ErrorCode::Verify);
+        assert_eq!(send(&client, delay_deadline(10)).unwrap_err().code, ErrorCode::HttpServer);
+        assert_eq!(received.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn deadline_test() {
+        let (url, received) = serve("/callback", statuses(&[503, 503]));
+        let client = CallbackClient::new(&url, Duration::from_secs(3)).unwrap().retry(5, Duration::from_secs(5));
+        let rtn = send(&client, Local::now().timestamp_millis() - 1);
+        assert_eq!(rtn.unwrap_err().kind(), ErrorKind::Logical);
+        // no time to wait for the next retry
+        assert_eq!(send(&client, delay_deadline(1)).unwrap_err().code, ErrorCode::HttpServer);
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn sign_test() {
+        // RFC 4231 test case 2
+        assert_eq!(sign_payload(b"Jefe", b"what do ya want for nothing?"), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        let (url, received) = serve("/callback", statuses(&[200]));
+        let client = CallbackClient::new(&url, Duration::from_secs(3)).unwrap().secret(b"key");
+        assert_eq!(send(&client, delay_deadline(10)), Ok(()));
+        let request = received.lock().unwrap()[0].to_lowercase();
+        let payload = serde_json::to_string(&delayed()).unwrap();
+        let expected = format!("x-nature-signature: sha256={}", sign_payload(b"key", payload.as_bytes()));
+        assert_eq!(request.contains(&expected), true);
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{BizObject, FromInstance, ID, Instance, is_default, NatureError, Result};
+
+/// The version of the canonical encoding, it will be increased when the layout changed.
+pub const CODEC_VERSION: u32 = 1;
+
+/// Each encoded `Instance` is wrapped with the version.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Envelope {
+    version: u32,
+    instance: CanonicalInstance,
+}
+
+/// The same fields as `Instance`, but maps and sets are sorted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct CanonicalInstance {
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    id: ID,
+    data: CanonicalBizObject,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    create_time: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct CanonicalBizObject {
+    meta: String,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    content: String,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    context: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    sys_context: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    states: BTreeSet<String>,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    state_version: i32,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    from: Option<FromInstance>,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    para: String,
+}
+
+impl From<&Instance> for Envelope {
+    fn from(ins: &Instance) -> Self {
+        let data = &ins.data;
+        Envelope {
+            version: CODEC_VERSION,
+            instance: CanonicalInstance {
+                id: ins.id,
+                data: CanonicalBizObject {
+                    meta: data.meta.clone(),
+                    content: data.content.clone(),
+                    context: data.context.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+                    sys_context: data.sys_context.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+                    states: data.states.iter().cloned().collect(),
+                    state_version: data.state_version,
+                    from: data.from.clone(),
+                    para: data.para.clone(),
+                },
+                create_time: ins.create_time,
+            },
+        }
+    }
+}
+
+impl Envelope {
+    fn into_instance(self) -> Result<Instance> {
+        if self.version != CODEC_VERSION {
+            let msg = format!("unsupported codec version: {}, expected: {}", self.version, CODEC_VERSION);
+            warn!("{}", &msg);
+            return Err(NatureError::VerifyError(msg));
+        }
+        let ins = self.instance;
+        let data = ins.data;
+        Ok(Instance {
+            id: ins.id,
+            data: BizObject {
+                meta: data.meta,
+                content: data.content,
+                context: data.context.into_iter().collect(),
+                sys_context: data.sys_context.into_iter().collect(),
+                states: data.states.into_iter().collect(),
+                state_version: data.state_version,
+                from: data.from,
+                para: data.para,
+            },
+            create_time: ins.create_time,
+        })
+    }
+}
+
+impl Instance {
+    /// The same `Instance` always get the same json: fields in declared order, maps and sets are sorted.
+    pub fn to_canonical_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&Envelope::from(self))?)
+    }
+
+    pub fn from_canonical_json(json: &str) -> Result<Instance> {
+        serde_json::from_str::<Envelope>(json)?.into_instance()
+    }
+
+    /// canonical form in CBOR
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor(&self) -> Result<Vec<u8>> {
+        Ok(serde_cbor::to_vec(&Envelope::from(self))?)
+    }
+
+    #[cfg(feature = "cbor")]
+    pub fn from_cbor(bytes: &[u8]) -> Result<Instance> {
+        serde_cbor::from_slice::<Envelope>(bytes)?.into_instance()
+    }
+
+    /// canonical form in MessagePack, the fields are encoded with names.
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self) -> Result<Vec<u8>> {
+        Ok(rmp_serde::to_vec_named(&Envelope::from(self))?)
+    }
+
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Instance> {
+        rmp_serde::from_slice::<Envelope>(bytes)?.into_instance()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn instance() -> Instance {
+        let mut ins = Instance::new("sale/order").unwrap();
+        ins.id = 123;
+        ins.content = "hello".to_string();
+        ins.context.insert("b".to_string(), "2".to_string());
+        ins.context.insert("a".to_string(), "1".to_string());
+        ins.sys_context.insert("target.id".to_string(), "5".to_string());
+        ins.states.insert("paid".to_string());
+        ins.states.insert("new".to_string());
+        ins.state_version = 2;
+        ins.from = Some(FromInstance {
+            id: 9,
+            meta: "B:sale/cart:1".to_string(),
+            para: "p".to_string(),
+            state_version: 1,
+        });
+        ins.create_time = 1000;
+        ins
+    }
+
+    #[test]
+    fn canonical_json_test() {
+        let ins = instance();
+        let json = ins.to_canonical_json().unwrap();
+        assert_eq!(json, r#"{"version":1,"instance":{"id":123,"data":{"meta":"B:sale/order:1","content":"hello","context":{"a":"1","b":"2"},"sys_context":{"target.id":"5"},"states":["new","paid"],"state_version":2,"from":{"id":9,"meta":"B:sale/cart:1","para":"p","state_version":1}},"create_time":1000}}"#);
+        assert_eq!(Instance::from_canonical_json(&json).unwrap(), ins);
+
+        // insert in different order
+        let mut other = instance();
+        other.context.clear();
+        other.context.insert("a".to_string(), "1".to_string());
+        other.context.insert("b".to_string(), "2".to_string());
+        assert_eq!(other.to_canonical_json().unwrap(), json);
+    }
+
+    #[test]
+    fn default_fields_omitted() {
+        let ins = Instance::new("a").unwrap();
+        let json = ins.to_canonical_json().unwrap();
+        assert_eq!(json, r#"{"version":1,"instance":{"data":{"meta":"B:a:1"}}}"#);
+        assert_eq!(Instance::from_canonical_json(&json).unwrap(), ins);
+    }
+
+    #[test]
+    fn version_test() {
+        let rtn = Instance::from_canonical_json(r#"{"version":2,"instance":{"data":{"meta":"B:a:1"}}}"#);
+        assert_eq!(rtn, Err(NatureError::VerifyError("unsupported codec version: 2, expected: 1".to_string())));
+        assert_eq!(Instance::from_canonical_json(r#"{"instance":{"data":{"meta":"B:a:1"}}}"#).is_err(), true);
+    }
+
+    #[test]
+    #[cfg(feature = "cbor")]
+    fn cbor_test() {
+        let ins = instance();
+        let bytes = ins.to_cbor().unwrap();
+        assert_eq!(Instance::from_cbor(&bytes).unwrap(), ins);
+        assert_eq!(bytes.len() < ins.to_canonical_json().unwrap().len(), true);
+        assert_eq!(Instance::from_cbor(&bytes[1..]).is_err(), true);
+    }
+
+    #[test]
+    #[cfg(feature = "msgpack")]
+    fn msgpack_test() {
+        let ins = instance();
+        let bytes = ins.to_msgpack().unwrap();
+        assert_eq!(Instance::from_msgpack(&bytes).unwrap(), ins);
+        assert_eq!(bytes.len() < ins.to_canonical_json().unwrap().len(), true);
+        assert_eq!(Instance::from_msgpack(&bytes[1..]).is_err(), true);
+    }
+}
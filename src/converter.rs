@@ -1,5 +1,10 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+
 use crate::{is_default, Result, SelfRouteInstance};
 use crate::error::NatureError;
 
@@ -60,8 +65,9 @@ pub struct DynamicConverter {
 }
 
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Ord, PartialOrd, Eq, Hash)]
-#[serde(rename_all = "camelCase")]
+/// serialized in camelCase, and `from_str` accepts the same names in any case.
+/// other names are parsed to `Custom` in lowercase, which are served by `register_protocol`.
+#[derive(Debug, Clone, PartialEq, Ord, PartialOrd, Eq, Hash)]
 pub enum Protocol {
     LocalRust,
     Http,
@@ -69,26 +75,64 @@ pub enum Protocol {
     /// Nature will automatically implement the converter. it can't be used by user.
     Auto,
     BuiltIn,
+    Custom(String),
+}
+
+impl Protocol {
+    pub fn name(&self) -> &str {
+        match self {
+            Protocol::LocalRust => "localRust",
+            Protocol::Http => "http",
+            Protocol::Https => "https",
+            Protocol::Auto => "auto",
+            Protocol::BuiltIn => "builtIn",
+            Protocol::Custom(name) => name,
+        }
+    }
 }
 
 impl FromStr for Protocol {
     type Err = NatureError;
 
     fn from_str(s: &str) -> Result<Self> {
-        let cmp = &*s.to_uppercase();
+        let cmp = &*s.to_lowercase();
         match cmp {
-            "LOCALRUST" => Ok(Protocol::LocalRust),
-            "HTTP" => Ok(Protocol::Http),
-            "HTTPS" => Ok(Protocol::Https),
-            "BUILTIN" => Ok(Protocol::BuiltIn),
+            "localrust" => Ok(Protocol::LocalRust),
+            "http" => Ok(Protocol::Http),
+            "https" => Ok(Protocol::Https),
+            "auto" => Ok(Protocol::Auto),
+            "builtin" => Ok(Protocol::BuiltIn),
             _ => {
-                let msg = format!("unknown protocol : {}", s);
-                Err(NatureError::VerifyError(msg))
+                let legal = cmp.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+                if cmp.is_empty() || !legal {
+                    let msg = format!("unknown protocol : {}", s);
+                    return Err(NatureError::VerifyError(msg));
+                }
+                Ok(Protocol::Custom(cmp.to_string()))
             }
         }
     }
 }
 
+impl Display for Protocol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Serialize for Protocol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Protocol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Protocol::from_str(&s).map_err(|e| D::Error::custom(e.to_string()))
+    }
+}
+
 impl Default for Protocol {
     fn default() -> Self {
         Protocol::LocalRust
@@ -141,4 +185,28 @@ mod test {
         let ewe_dw: Executor = serde_json::from_str(&ewe_s).unwrap();
         assert_eq!(ewe_dw, exe);
     }
+
+    #[test]
+    fn protocol_round_trip() {
+        let all = vec![Protocol::LocalRust, Protocol::Http, Protocol::Https, Protocol::Auto, Protocol::BuiltIn, Protocol::Custom("grpc".to_string())];
+        for one in all {
+            let json = serde_json::to_string(&one).unwrap();
+            assert_eq!(serde_json::from_str::<Protocol>(&json).unwrap(), one);
+            assert_eq!(Protocol::from_str(&one.to_string()).unwrap(), one);
+        }
+        assert_eq!(serde_json::to_string(&Protocol::BuiltIn).unwrap(), r#""builtIn""#);
+        assert_eq!(Protocol::from_str("AUTO").unwrap(), Protocol::Auto);
+        assert_eq!(Protocol::from_str("LocalRust").unwrap(), Protocol::LocalRust);
+        assert_eq!(Protocol::from_str("Wasm").unwrap(), Protocol::Custom("wasm".to_string()));
+        assert_eq!(Protocol::from_str("").is_err(), true);
+        assert_eq!(Protocol::from_str("a b").is_err(), true);
+    }
+
+    #[test]
+    fn serde_custom_executor() {
+        let exe: Executor = serde_json::from_str(r#"{"protocol":"unix","url":"/tmp/a.sock"}"#).unwrap();
+        assert_eq!(exe.protocol, Protocol::Custom("unix".to_string()));
+        assert_eq!(serde_json::to_string(&exe).unwrap(), r#"{"protocol":"unix","url":"/tmp/a.sock"}"#);
+        assert_eq!(serde_json::from_str::<Executor>(r#"{"protocol":"a/b"}"#).is_err(), true);
+    }
 }
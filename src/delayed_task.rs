@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use chrono::prelude::*;
+
+use crate::{ConverterReturned, delay_deadline, DelayedInstances, NatureError, Result};
+
+/// The message of `ConverterReturned::EnvError` fired by the `DelayedTaskTimer`
+pub static DELAYED_TASK_TIMEOUT: &str = "timeout";
+
+/// A task which is waiting for the callback of the converter, the times are milliseconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DelayedTask {
+    pub task_id: String,
+    pub issued_at: i64,
+    pub deadline: i64,
+    /// increased by `retry`
+    #[serde(default)]
+    pub attempt: u32,
+}
+
+impl DelayedTask {
+    /// for `ConverterReturned::Delay`
+    pub fn new(task_id: &str, seconds: u32) -> Self {
+        DelayedTask {
+            task_id: task_id.to_string(),
+            issued_at: Local::now().timestamp_millis(),
+            deadline: delay_deadline(seconds),
+            attempt: 0,
+        }
+    }
+
+    /// for `DynamicConverter::delay`, which should not be negative
+    pub fn from_delay(task_id: &str, seconds: i32) -> Result<Self> {
+        if seconds < 0 {
+            return Err(NatureError::VerifyError(format!("delay should not be negative, but get {}", seconds)));
+        }
+        Ok(DelayedTask::new(task_id, seconds as u32))
+    }
+
+    /// wait for another `seconds` from now
+    pub fn retry(&mut self, seconds: u32) {
+        self.attempt += 1;
+        self.issued_at = Local::now().timestamp_millis();
+        self.deadline = delay_deadline(seconds);
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.deadline
+    }
+
+    /// the result which should be used if the callback never arrives
+    pub fn timeout_result(&self) -> DelayedInstances {
+        DelayedInstances {
+            task_id: self.task_id.to_string(),
+            result: ConverterReturned::EnvError(DELAYED_TASK_TIMEOUT.to_string()),
+        }
+    }
+}
+
+/// A hashed timer wheel for `DelayedTask`s, it's driven by `advance`.
+/// A task never fires before its deadline, but may be later for at most one tick.
+#[derive(Debug, Clone)]
+pub struct TimerWheel {
+    tick: i64,
+    /// the tasks with the tick number they should be fired at
+    slots: Vec<Vec<(i64, DelayedTask)>>,
+    /// the next tick number to be processed
+    next_tick: i64,
+    /// task_id → slot index
+    index: HashMap<String, usize>,
+}
+
+impl TimerWheel {
+    /// `now` is the milliseconds to begin with
+    pub fn new(tick: Duration, slots: usize, now: i64) -> Self {
+        let tick = (tick.as_millis() as i64).max(1);
+        TimerWheel {
+            tick,
+            slots: vec![vec![]; slots.max(1)],
+            next_tick: now / tick,
+            index: HashMap::new(),
+        }
+    }
+
+    /// the task with the same `task_id` will be replaced
+    pub fn add(&mut self, task: DelayedTask) {
+        self.cancel(&task.task_id);
+        // round up, so that it will not be fired before the deadline
+        let tick_no = ((task.deadline + self.tick - 1) / self.tick).max(self.next_tick);
+        let slot = (tick_no % self.slots.len() as i64) as usize;
+        self.index.insert(task.task_id.to_string(), slot);
+        self.slots[slot].push((tick_no, task));
+    }
+
+    /// remove the task for the arrived callback
+    pub fn cancel(&mut self, task_id: &str) -> Option<DelayedTask> {
+        let slot = self.index.remove(task_id)?;
+        let pos = self.slots[slot].iter().position(|(_, t)| t.task_id == task_id)?;
+        Some(self.slots[slot].remove(pos).1)
+    }
+
+    /// move to `now` and return the expired tasks in deadline order
+    pub fn advance(&mut self, now: i64) -> Vec<DelayedTask> {
+        let target = now / self.tick;
+        if target < self.next_tick {
+            return vec![];
+        }
+        let len = self.slots.len() as i64;
+        let steps = (target - self.next_tick + 1).min(len);
+        let mut rtn: Vec<DelayedTask> = vec![];
+        for i in 0..steps {
+            let slot = ((self.next_tick + i) % len) as usize;
+            let (fired, remained): (Vec<_>, Vec<_>) = self.slots[slot].drain(..).partition(|(no, _)| *no <= target);
+            self.slots[slot] = remained;
+            fired.into_iter().for_each(|(_, task)| {
+                self.index.remove(&task.task_id);
+                rtn.push(task);
+            });
+        }
+        self.next_tick = target + 1;
+        rtn.sort_by_key(|t| t.deadline);
+        rtn
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+}
+
+/// Drive a `TimerWheel` by a thread, the timeout `DelayedInstances` will be sent to the `Sender`.
+/// The thread stops when this is dropped or the receiver is dropped.
+pub struct DelayedTaskTimer {
+    wheel: Arc<Mutex<TimerWheel>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl DelayedTaskTimer {
+    pub fn start(tick: Duration, slots: usize, sender: Sender<DelayedInstances>) -> Self {
+        let wheel = Arc::new(Mutex::new(TimerWheel::new(tick, slots, Local::now().timestamp_millis())));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let w = wheel.clone();
+        let s = stopped.clone();
+        thread::spawn(move || {
+            while !s.load(Ordering::Relaxed) {
+                thread::sleep(tick);
+                let fired = w.lock().unwrap().advance(Local::now().timestamp_millis());
+                for task in fired {
+                    warn!("delayed task [{}] timeout, attempt: {}", task.task_id, task.attempt);
+                    if sender.send(task.timeout_result()).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        DelayedTaskTimer { wheel, stopped }
+    }
+
+    pub fn add(&self, task: DelayedTask) {
+        self.wheel.lock().unwrap().add(task)
+    }
+
+    pub fn cancel(&self, task_id: &str) -> Option<DelayedTask> {
+        self.wheel.lock().unwrap().cancel(task_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.wheel.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wheel.lock().unwrap().is_empty()
+    }
+}
+
+impl Drop for DelayedTaskTimer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+
+    fn task(id: &str, deadline: i64) -> DelayedTask {
+        DelayedTask { task_id: id.to_string(), issued_at: 0, deadline, attempt: 0 }
+    }
+
+    #[test]
+    fn task_test() {
+        let mut t = DelayedTask::new("t1", 10);
+        assert_eq!(t.deadline - t.issued_at >= 10_000, true);
+        assert_eq!(t.is_expired(t.deadline - 1), false);
+        assert_eq!(t.is_expired(t.deadline), true);
+        t.retry(5);
+        assert_eq!(t.attempt, 1);
+        assert_eq!(DelayedTask::from_delay("t", -1).is_err(), true);
+        assert_eq!(DelayedTask::from_delay("t", 1).is_ok(), true);
+        let rtn = t.timeout_result();
+        assert_eq!(rtn.task_id, "t1");
+        assert_eq!(rtn.result, ConverterReturned::EnvError("timeout".to_string()));
+    }
+
+    #[test]
+    fn wheel_test() {
+        let mut wheel = TimerWheel::new(Duration::from_millis(10), 8, 1000);
+        wheel.add(task("a", 1025));
+        wheel.add(task("b", 1015));
+        // more than one round
+        wheel.add(task("c", 1200));
+        wheel.add(task("d", 1030));
+        assert_eq!(wheel.len(), 4);
+        assert_eq!(wheel.cancel("d").is_some(), true);
+        assert_eq!(wheel.cancel("d"), None);
+        assert_eq!(wheel.advance(1014).is_empty(), true);
+        let ids: Vec<String> = wheel.advance(1030).into_iter().map(|t| t.task_id).collect();
+        assert_eq!(ids, vec!["b", "a"]);
+        assert_eq!(wheel.advance(1199).is_empty(), true);
+        assert_eq!(wheel.advance(1200)[0].task_id, "c");
+        assert_eq!(wheel.is_empty(), true);
+    }
+
+    #[test]
+    fn wheel_edge_test() {
+        let mut wheel = TimerWheel::new(Duration::from_millis(10), 4, 1000);
+        // already expired
+        wheel.add(task("a", 900));
+        // replaced
+        wheel.add(task("b", 1010));
+        wheel.add(task("b", 5000));
+        assert_eq!(wheel.len(), 2);
+        let rtn = wheel.advance(1000);
+        assert_eq!(rtn.len(), 1);
+        assert_eq!(rtn[0].task_id, "a");
+        // jump over many rounds
+        assert_eq!(wheel.advance(4999).is_empty(), true);
+        assert_eq!(wheel.advance(100_000)[0].task_id, "b");
+        // go back
+        wheel.add(task("c", 100_005));
+        assert_eq!(wheel.advance(50).is_empty(), true);
+        assert_eq!(wheel.len(), 1);
+    }
+
+    #[test]
+    fn timer_test() {
+        let (sender, receiver) = channel();
+        let timer = DelayedTaskTimer::start(Duration::from_millis(5), 16, sender);
+        let now = Local::now().timestamp_millis();
+        timer.add(task("a", now + 20));
+        timer.add(task("b", now + 20));
+        timer.add(task("c", now + 10_000));
+        assert_eq!(timer.cancel("b").is_some(), true);
+        let rtn = receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(rtn.task_id, "a");
+        assert_eq!(Local::now().timestamp_millis() >= now + 20, true);
+        assert_eq!(receiver.recv_timeout(Duration::from_millis(100)).is_err(), true);
+        assert_eq!(timer.len(), 1);
+    }
+}
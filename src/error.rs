@@ -4,6 +4,8 @@ use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::sync::mpsc::SendError;
 
+use crate::ConverterReturned;
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum NatureError {
     VerifyError(String),
@@ -13,6 +15,259 @@ pub enum NatureError {
     EnvironmentError(String),
 }
 
+/// The category of `NatureError`, one for each simple variant.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    Verify,
+    Logical,
+    DaoDuplicated,
+    System,
+    Environment,
+}
+
+/// Stable codes of `NatureError`, the thousands place is the `ErrorKind`, never change the numbers.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    Verify = 1000,
+    Json = 1001,
+    ParseInt = 1002,
+    Logical = 2000,
+    /// http 4xx except 408 and 429
+    HttpClient = 2001,
+    /// the request can't be built
+    HttpRequest = 2002,
+    DaoDuplicated = 3000,
+    System = 4000,
+    Environment = 5000,
+    Timeout = 5001,
+    Connect = 5002,
+    /// http 5xx
+    HttpServer = 5003,
+    /// http 429
+    HttpThrottled = 5004,
+    Io = 5005,
+    Channel = 5006,
+}
+
+impl ErrorCode {
+    pub fn number(&self) -> u16 {
+        *self as u16
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorCode::Verify => "verify",
+            ErrorCode::Json => "json",
+            ErrorCode::ParseInt => "parse_int",
+            ErrorCode::Logical => "logical",
+            ErrorCode::HttpClient => "http_client",
+            ErrorCode::HttpRequest => "http_request",
+            ErrorCode::DaoDuplicated => "dao_duplicated",
+            ErrorCode::System => "system",
+            ErrorCode::Environment => "environment",
+            ErrorCode::Timeout => "timeout",
+            ErrorCode::Connect => "connect",
+            ErrorCode::HttpServer => "http_server",
+            ErrorCode::HttpThrottled => "http_throttled",
+            ErrorCode::Io => "io",
+            ErrorCode::Channel => "channel",
+        }
+    }
+
+    pub fn from_number(number: u16) -> Option<ErrorCode> {
+        let rtn = match number {
+            1000 => ErrorCode::Verify,
+            1001 => ErrorCode::Json,
+            1002 => ErrorCode::ParseInt,
+            2000 => ErrorCode::Logical,
+            2001 => ErrorCode::HttpClient,
+            2002 => ErrorCode::HttpRequest,
+            3000 => ErrorCode::DaoDuplicated,
+            4000 => ErrorCode::System,
+            5000 => ErrorCode::Environment,
+            5001 => ErrorCode::Timeout,
+            5002 => ErrorCode::Connect,
+            5003 => ErrorCode::HttpServer,
+            5004 => ErrorCode::HttpThrottled,
+            5005 => ErrorCode::Io,
+            5006 => ErrorCode::Channel,
+            _ => return None
+        };
+        Some(rtn)
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self.number() / 1000 {
+            1 => ErrorKind::Verify,
+            2 => ErrorKind::Logical,
+            3 => ErrorKind::DaoDuplicated,
+            4 => ErrorKind::System,
+            _ => ErrorKind::Environment,
+        }
+    }
+}
+
+/// `NatureError` with a code, context and source, it keeps the shape of `NatureError` unchanged.
+/// It can be converted into `NatureError` of the same `ErrorKind`, the code and context are kept in the message.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DetailedError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// meta string of the `Instance` being processed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub meta: Option<String>,
+    /// key of the `Instance` being processed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub task_id: Option<String>,
+    /// the error which caused this one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub source: Option<Box<DetailedError>>,
+}
+
+impl NatureError {
+    pub fn from_kind(kind: ErrorKind, message: &str) -> Self {
+        let message = message.to_string();
+        match kind {
+            ErrorKind::Verify => NatureError::VerifyError(message),
+            ErrorKind::Logical => NatureError::LogicalError(message),
+            ErrorKind::DaoDuplicated => NatureError::DaoDuplicated(message),
+            ErrorKind::System => NatureError::SystemError(message),
+            ErrorKind::Environment => NatureError::EnvironmentError(message),
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            NatureError::VerifyError(_) => ErrorKind::Verify,
+            NatureError::LogicalError(_) => ErrorKind::Logical,
+            NatureError::DaoDuplicated(_) => ErrorKind::DaoDuplicated,
+            NatureError::SystemError(_) => ErrorKind::System,
+            NatureError::EnvironmentError(_) => ErrorKind::Environment,
+        }
+    }
+
+    /// the generic code of the kind
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::from_kind(self.kind())
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            NatureError::VerifyError(msg) => msg,
+            NatureError::LogicalError(msg) => msg,
+            NatureError::DaoDuplicated(msg) => msg,
+            NatureError::SystemError(msg) => msg,
+            NatureError::EnvironmentError(msg) => msg,
+        }
+    }
+
+    /// Only the errors of `ErrorKind::Environment` may be succeed when retry.
+    pub fn is_retryable(&self) -> bool {
+        self.kind() == ErrorKind::Environment
+    }
+
+    pub fn with_meta(self, meta: &str) -> DetailedError {
+        DetailedError::from(self).with_meta(meta)
+    }
+
+    pub fn with_instance(self, key: &str) -> DetailedError {
+        DetailedError::from(self).with_instance(key)
+    }
+
+    pub fn with_task(self, task_id: &str) -> DetailedError {
+        DetailedError::from(self).with_task(task_id)
+    }
+
+    pub fn with_source<E: Into<DetailedError>>(self, source: E) -> DetailedError {
+        DetailedError::from(self).with_source(source)
+    }
+}
+
+impl ErrorCode {
+    /// the code ends with `000` for the kind
+    pub fn from_kind(kind: ErrorKind) -> ErrorCode {
+        match kind {
+            ErrorKind::Verify => ErrorCode::Verify,
+            ErrorKind::Logical => ErrorCode::Logical,
+            ErrorKind::DaoDuplicated => ErrorCode::DaoDuplicated,
+            ErrorKind::System => ErrorCode::System,
+            ErrorKind::Environment => ErrorCode::Environment,
+        }
+    }
+
+    pub fn is_generic(&self) -> bool {
+        *self == ErrorCode::from_kind(self.kind())
+    }
+}
+
+impl DetailedError {
+    pub fn new(code: ErrorCode, message: &str) -> Self {
+        DetailedError {
+            code,
+            message: message.to_string(),
+            meta: None,
+            instance: None,
+            task_id: None,
+            source: None,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.code.kind()
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind() == ErrorKind::Environment
+    }
+
+    /// no code except the generic one, and no context
+    pub fn is_plain(&self) -> bool {
+        self.code.is_generic() && self.meta.is_none() && self.instance.is_none() && self.task_id.is_none() && self.source.is_none()
+    }
+
+    pub fn with_meta(mut self, meta: &str) -> Self {
+        self.meta = Some(meta.to_string());
+        self
+    }
+
+    pub fn with_instance(mut self, key: &str) -> Self {
+        self.instance = Some(key.to_string());
+        self
+    }
+
+    pub fn with_task(mut self, task_id: &str) -> Self {
+        self.task_id = Some(task_id.to_string());
+        self
+    }
+
+    pub fn with_source<E: Into<DetailedError>>(mut self, source: E) -> Self {
+        self.source = Some(Box::new(source.into()));
+        self
+    }
+}
+
+impl From<NatureError> for DetailedError {
+    fn from(e: NatureError) -> Self {
+        DetailedError::new(e.code(), e.message())
+    }
+}
+
+/// The message is the `Display` of `DetailedError` unless it is plain.
+impl From<DetailedError> for NatureError {
+    fn from(e: DetailedError) -> Self {
+        match e.is_plain() {
+            true => NatureError::from_kind(e.kind(), &e.message),
+            false => NatureError::from_kind(e.kind(), &e.to_string())
+        }
+    }
+}
+
 impl Error for NatureError {}
 
 impl Display for NatureError {
@@ -21,6 +276,51 @@ impl Display for NatureError {
     }
 }
 
+impl Error for DetailedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|e| e.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+impl Display for DetailedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[{} {}] {}", self.code.number(), self.code.name(), self.message)?;
+        if let Some(meta) = &self.meta {
+            write!(f, ", meta: {}", meta)?;
+        }
+        if let Some(ins) = &self.instance {
+            write!(f, ", instance: {}", ins)?;
+        }
+        if let Some(task) = &self.task_id {
+            write!(f, ", task: {}", task)?;
+        }
+        if let Some(source) = &self.source {
+            write!(f, ", caused by: {}", source)?;
+        }
+        Ok(())
+    }
+}
+
+/// retryable errors are `EnvError`, others are `LogicalError`
+impl From<NatureError> for ConverterReturned {
+    fn from(e: NatureError) -> Self {
+        match e.is_retryable() {
+            true => ConverterReturned::EnvError(e.to_string()),
+            false => ConverterReturned::LogicalError(e.to_string())
+        }
+    }
+}
+
+/// retryable errors are `EnvError`, others are `LogicalError`
+impl From<DetailedError> for ConverterReturned {
+    fn from(e: DetailedError) -> Self {
+        match e.is_retryable() {
+            true => ConverterReturned::EnvError(e.to_string()),
+            false => ConverterReturned::LogicalError(e.to_string())
+        }
+    }
+}
+
 impl From<serde_json::error::Error> for NatureError {
     fn from(e: serde_json::error::Error) -> Self {
         NatureError::VerifyError(e.to_string())
@@ -39,9 +339,74 @@ impl<T> From<SendError<T>> for NatureError {
     }
 }
 
+/// Only the 4xx responses except 408 and 429 and the unbuildable requests are `LogicalError`,
+/// they will never succeed on retry, others are `EnvironmentError` as before.
 impl From<reqwest::Error> for NatureError {
     fn from(err: reqwest::Error) -> Self {
-        NatureError::EnvironmentError(err.to_string())
+        DetailedError::from(err).into()
+    }
+}
+
+impl From<serde_json::error::Error> for DetailedError {
+    fn from(e: serde_json::error::Error) -> Self {
+        DetailedError::new(ErrorCode::Json, &e.to_string())
+    }
+}
+
+impl From<std::num::ParseIntError> for DetailedError {
+    fn from(e: std::num::ParseIntError) -> Self {
+        DetailedError::new(ErrorCode::ParseInt, &e.to_string())
+    }
+}
+
+impl<T> From<SendError<T>> for DetailedError {
+    fn from(err: SendError<T>) -> Self {
+        DetailedError::new(ErrorCode::Channel, &err.to_string())
+    }
+}
+
+impl From<std::io::Error> for DetailedError {
+    fn from(err: std::io::Error) -> Self {
+        DetailedError::new(ErrorCode::Io, &err.to_string())
+    }
+}
+
+impl From<reqwest::Error> for DetailedError {
+    fn from(err: reqwest::Error) -> Self {
+        let msg = err.to_string();
+        let code = if let Some(status) = err.status() {
+            ErrorCode::from_http_status(status.as_u16())
+        } else if err.is_timeout() {
+            ErrorCode::Timeout
+        } else if err.is_connect() {
+            ErrorCode::Connect
+        } else if err.is_builder() {
+            ErrorCode::HttpRequest
+        } else {
+            ErrorCode::Environment
+        };
+        DetailedError::new(code, &msg)
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl From<serde_cbor::Error> for NatureError {
+    fn from(e: serde_cbor::Error) -> Self {
+        NatureError::VerifyError(e.to_string())
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl From<rmp_serde::encode::Error> for NatureError {
+    fn from(e: rmp_serde::encode::Error) -> Self {
+        NatureError::VerifyError(e.to_string())
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl From<rmp_serde::decode::Error> for NatureError {
+    fn from(e: rmp_serde::decode::Error) -> Self {
+        NatureError::VerifyError(e.to_string())
     }
 }
 
@@ -50,3 +415,89 @@ impl From<std::io::Error> for NatureError {
         NatureError::EnvironmentError(err.to_string())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn code_test() {
+        assert_eq!(NatureError::VerifyError("a".to_string()).code(), ErrorCode::Verify);
+        assert_eq!(ErrorCode::HttpClient.number(), 2001);
+        assert_eq!(ErrorCode::HttpClient.name(), "http_client");
+        assert_eq!(ErrorCode::HttpClient.kind(), ErrorKind::Logical);
+        assert_eq!(ErrorCode::from_number(2001), Some(ErrorCode::HttpClient));
+        assert_eq!(ErrorCode::from_number(2999), None);
+        assert_eq!(ErrorCode::Channel.kind(), ErrorKind::Environment);
+        assert_eq!(DetailedError::new(ErrorCode::Json, "bad").kind(), ErrorKind::Verify);
+    }
+
+    #[test]
+    fn from_test() {
+        let e = serde_json::from_str::<u8>("a").unwrap_err();
+        assert_eq!(DetailedError::from(e).code, ErrorCode::Json);
+        let e = serde_json::from_str::<u8>("a").unwrap_err();
+        assert_eq!(NatureError::from(e).kind(), ErrorKind::Verify);
+        assert_eq!(DetailedError::from("a".parse::<u8>().unwrap_err()).code, ErrorCode::ParseInt);
+        let e = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert_eq!(NatureError::from(e), NatureError::EnvironmentError("entity not found".to_string()));
+        let e = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert_eq!(DetailedError::from(e).code, ErrorCode::Io);
+        let (sender, receiver) = std::sync::mpsc::channel::<u8>();
+        drop(receiver);
+        let e = sender.send(1).unwrap_err();
+        assert_eq!(NatureError::from(e).kind(), ErrorKind::Environment);
+    }
+
+    #[test]
+    fn retryable_test() {
+        assert_eq!(NatureError::EnvironmentError("a".to_string()).is_retryable(), true);
+        assert_eq!(NatureError::LogicalError("a".to_string()).is_retryable(), false);
+        assert_eq!(DetailedError::new(ErrorCode::HttpThrottled, "").is_retryable(), true);
+        assert_eq!(DetailedError::new(ErrorCode::HttpClient, "").is_retryable(), false);
+        let e: DetailedError = reqwest::Client::new().get("not a url").build().unwrap_err().into();
+        assert_eq!(e.code, ErrorCode::HttpRequest);
+        assert_eq!(e.is_retryable(), false);
+        let e: NatureError = reqwest::Client::new().get("not a url").build().unwrap_err().into();
+        assert_eq!(e.kind(), ErrorKind::Logical);
+    }
+
+    #[test]
+    fn context_test() {
+        let e = NatureError::VerifyError("bad content".to_string())
+            .with_meta("B:a:1")
+            .with_instance("B:a:1|1||0")
+            .with_task("t1")
+            .with_source(DetailedError::new(ErrorCode::Json, "eof"));
+        assert_eq!(e.kind(), ErrorKind::Verify);
+        assert_eq!(e.message, "bad content");
+        assert_eq!(e.task_id, Some("t1".to_string()));
+        assert_eq!(e.source().unwrap().to_string(), "[1001 json] eof");
+        assert_eq!(e.to_string(), "[1000 verify] bad content, meta: B:a:1, instance: B:a:1|1||0, task: t1, caused by: [1001 json] eof");
+        let json = serde_json::to_string(&e).unwrap();
+        assert_eq!(serde_json::from_str::<DetailedError>(&json).unwrap(), e);
+        assert_eq!(NatureError::SystemError("a".to_string()).to_string(), r#"SystemError("a")"#);
+    }
+
+    #[test]
+    fn to_nature_error_test() {
+        let e = NatureError::from(DetailedError::from(NatureError::DaoDuplicated("a".to_string())));
+        assert_eq!(e, NatureError::DaoDuplicated("a".to_string()));
+        let e = NatureError::from(DetailedError::new(ErrorCode::Timeout, "slow").with_task("t1"));
+        assert_eq!(e, NatureError::EnvironmentError("[5001 timeout] slow, task: t1".to_string()));
+    }
+
+    #[test]
+    fn converter_returned_test() {
+        let rtn: ConverterReturned = DetailedError::new(ErrorCode::HttpServer, "down").into();
+        match rtn {
+            ConverterReturned::EnvError(msg) => assert_eq!(msg, "[5003 http_server] down"),
+            _ => panic!("should be EnvError")
+        }
+        let rtn: ConverterReturned = NatureError::VerifyError("bad".to_string()).into();
+        match rtn {
+            ConverterReturned::LogicalError(msg) => assert_eq!(msg, r#"VerifyError("bad")"#),
+            _ => panic!("should be LogicalError")
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use serde::de::DeserializeOwned;
+
+use crate::{built_in_converter, built_in_verifier, ConverterParameter, Executor, NatureError, Protocol, protocol_invoker, Result};
+
+/// The typed settings of an executor, parsed from `Executor::settings`.
+/// Fields absent from the settings should be `#[serde(default)]`.
+pub trait ExecutorSetting: DeserializeOwned + Default {
+    /// check the values after parsed
+    fn verify(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// check the settings string against a declared type
+pub type SettingVerifier = fn(&str) -> Result<()>;
+
+lazy_static! {
+    static ref SETTING_TYPES: RwLock<HashMap<(Protocol, String), SettingVerifier>> = RwLock::new(HashMap::new());
+}
+
+/// empty `settings` means `T::default()`, unknown fields are treated as errors.
+pub fn parse_setting<T: ExecutorSetting>(settings: &str) -> Result<T> {
+    let rtn: T = match settings.trim().is_empty() {
+        true => T::default(),
+        false => parse_known(settings)?
+    };
+    rtn.verify()?;
+    Ok(rtn)
+}
+
+fn parse_known<T: DeserializeOwned>(settings: &str) -> Result<T> {
+    let mut unknown: Vec<String> = vec![];
+    let mut de = serde_json::Deserializer::from_str(settings);
+    // `?` is the segment for `Option::Some`
+    let rtn: T = match serde_ignored::deserialize(&mut de, |path| unknown.push(path.to_string().replace(".?", ""))) {
+        Ok(rtn) => rtn,
+        Err(e) => return Err(NatureError::VerifyError(format!("illegal executor settings: {}, {}", settings, e)))
+    };
+    if !unknown.is_empty() {
+        let msg = format!("unknown fields [{}] in executor settings: {}", unknown.join(", "), settings);
+        warn!("{}", &msg);
+        return Err(NatureError::VerifyError(msg));
+    }
+    Ok(rtn)
+}
+
+/// a `SettingVerifier` for `T`
+pub fn verify_setting<T: ExecutorSetting>(settings: &str) -> Result<()> {
+    parse_setting::<T>(settings).map(|_| ())
+}
+
+/// declare the settings type for the executor, it will be used by `Executor::verify_settings`.
+/// the built-in executors are declared by this crate.
+pub fn declare_setting<T: ExecutorSetting>(protocol: Protocol, url: &str) {
+    SETTING_TYPES.write().unwrap().insert((protocol, url.to_string()), verify_setting::<T>);
+}
+
+impl Executor {
+    pub fn setting<T: ExecutorSetting>(&self) -> Result<T> {
+        parse_setting(&self.settings)
+    }
+
+    /// Check the settings when configured, so that the errors will not be delayed to the invocation.
+    /// executors without declared settings type are passed, but a `Protocol::Custom` must be registered,
+    /// so that a misspelled protocol will be found here.
+    pub fn verify_settings(&self) -> Result<()> {
+        if let Protocol::Custom(name) = &self.protocol {
+            if protocol_invoker(name).is_none() {
+                return Err(NatureError::VerifyError(format!("unregistered protocol: {}", name)));
+            }
+        }
+        let verifier = match self.protocol {
+            Protocol::BuiltIn => {
+                built_in_converter(&self.url)?;
+                built_in_verifier(&self.url)
+            }
+            _ => SETTING_TYPES.read().unwrap().get(&(self.protocol.clone(), self.url.to_string())).cloned()
+        };
+        match verifier {
+            Some(verify) => verify(&self.settings),
+            None => Ok(())
+        }
+    }
+}
+
+impl ConverterParameter {
+    /// parse `cfg`, which is the settings of the executor
+    pub fn setting<T: ExecutorSetting>(&self) -> Result<T> {
+        parse_setting(&self.cfg)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Deserialize, Debug, Clone, PartialEq)]
+    struct MySetting {
+        #[serde(default)]
+        name: String,
+        #[serde(default = "default_retry")]
+        retry: u8,
+        #[serde(default)]
+        inner: Option<Inner>,
+    }
+
+    #[derive(Deserialize, Debug, Clone, PartialEq)]
+    struct Inner {
+        a: i32,
+    }
+
+    fn default_retry() -> u8 { 3 }
+
+    impl Default for MySetting {
+        fn default() -> Self {
+            MySetting { name: "".to_string(), retry: default_retry(), inner: None }
+        }
+    }
+
+    impl ExecutorSetting for MySetting {
+        fn verify(&self) -> Result<()> {
+            match self.retry > 10 {
+                true => Err(NatureError::VerifyError("retry should not be greater than 10".to_string())),
+                false => Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(parse_setting::<MySetting>(""), Ok(MySetting::default()));
+        let rtn = parse_setting::<MySetting>(r#"{"name":"a"}"#).unwrap();
+        assert_eq!(rtn.retry, 3);
+        assert_eq!(rtn.name, "a");
+        let rtn = parse_setting::<MySetting>(r#"{"retry":11}"#);
+        assert_eq!(rtn, Err(NatureError::VerifyError("retry should not be greater than 10".to_string())));
+        assert_eq!(parse_setting::<MySetting>(r#"{"retry":"a"}"#).is_err(), true);
+    }
+
+    #[test]
+    fn unknown_fields_test() {
+        let rtn = parse_setting::<MySetting>(r#"{"nmae":"a","inner":{"a":1,"b":2}}"#);
+        assert_eq!(rtn, Err(NatureError::VerifyError(r#"unknown fields [nmae, inner.b] in executor settings: {"nmae":"a","inner":{"a":1,"b":2}}"#.to_string())));
+    }
+
+    #[test]
+    fn verify_settings_test() {
+        let mut exe = Executor {
+            protocol: Protocol::Http,
+            url: "http://localhost/my".to_string(),
+            settings: r#"{"retry":20}"#.to_string(),
+        };
+        // not declared
+        assert_eq!(exe.verify_settings(), Ok(()));
+        declare_setting::<MySetting>(Protocol::Http, "http://localhost/my");
+        assert_eq!(exe.verify_settings().is_err(), true);
+        exe.settings = r#"{"retry":2}"#.to_string();
+        assert_eq!(exe.verify_settings(), Ok(()));
+        assert_eq!(exe.setting::<MySetting>().unwrap().retry, 2);
+    }
+
+    #[test]
+    fn built_in_test() {
+        let mut exe = Executor {
+            protocol: Protocol::BuiltIn,
+            url: "projection".to_string(),
+            settings: r#"{"fields":["/a"]}"#.to_string(),
+        };
+        assert_eq!(exe.verify_settings(), Ok(()));
+        exe.settings = r#"{"field":["/a"]}"#.to_string();
+        assert_eq!(exe.verify_settings().is_err(), true);
+        exe.url = "none".to_string();
+        assert_eq!(exe.verify_settings(), Err(NatureError::VerifyError("unknown built-in executor: none".to_string())));
+    }
+}
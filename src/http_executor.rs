@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use reqwest::Client;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+
+use crate::{ConverterParameter, ConverterReturned, DetailedError, Executor, NatureError, Problem, Protocol, PROBLEM_CONTENT_TYPE, Result};
+
+/// Call the `Executor` of `Protocol::Http` or `Protocol::Https`.
+/// `ConverterParameter` is posted as json to the `Executor::url`, and a json of `ConverterReturned` is expected.
+/// A failed executor should response with a `Problem` json.
+#[derive(Debug, Clone)]
+pub struct HttpExecutorClient {
+    client: Client,
+    timeout: Duration,
+}
+
+impl HttpExecutorClient {
+    /// `timeout` is for the whole request, include connecting and reading the response.
+    pub fn new(timeout: Duration) -> Result<Self> {
+        let client = Client::builder().timeout(timeout).build()?;
+        Ok(HttpExecutorClient { client, timeout })
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// transport errors are mapped by `From<reqwest::Error>`, use `DetailedError::is_retryable` to decide retry.
+    pub async fn execute(&self, executor: &Executor, para: &ConverterParameter) -> std::result::Result<ConverterReturned, DetailedError> {
+        check_url(executor)?;
+        let response = self.client.post(&executor.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, format!("application/json, {}", PROBLEM_CONTENT_TYPE))
+            .body(serde_json::to_string(para)?)
+            .send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            let e = Problem::parse(status.as_u16(), &body).with_task(&para.task_id);
+            warn!("executor [{}] failed: {}", executor.url, e);
+            return Err(e);
+        }
+        let rtn: ConverterReturned = match serde_json::from_str(&body) {
+            Ok(rtn) => rtn,
+            Err(e) => {
+                let msg = format!("executor [{}] returned unrecognized content: {}", executor.url, e);
+                warn!("{}", &msg);
+                return Err(NatureError::LogicalError(msg).into());
+            }
+        };
+        if let ConverterReturned::Delay(0) = rtn {
+            let msg = format!("executor [{}] returned Delay(0), the delay seconds should be greater than 0", executor.url);
+            return Err(NatureError::LogicalError(msg).into());
+        }
+        Ok(rtn)
+    }
+}
+
+fn check_url(executor: &Executor) -> Result<()> {
+    let scheme = match executor.protocol {
+        Protocol::Http => "http://",
+        Protocol::Https => "https://",
+        _ => return Err(NatureError::VerifyError(format!("unsupported protocol for http executor: {:?}", executor.protocol)))
+    };
+    if !executor.url.to_lowercase().starts_with(scheme) {
+        let msg = format!("url of {:?} executor should start with {}, but get: {}", executor.protocol, scheme, executor.url);
+        return Err(NatureError::VerifyError(msg));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+
+    use actix::System;
+
+    use crate::{ErrorCode, ErrorKind, Instance};
+    use crate::test_server::serve;
+
+    use super::*;
+
+    fn para() -> ConverterParameter {
+        ConverterParameter {
+            from: Instance::new("a").unwrap(),
+            last_state: None,
+            task_id: "t1".to_string(),
+            master: None,
+            cfg: "".to_string(),
+        }
+    }
+
+    fn execute(url: &str, timeout: u64) -> std::result::Result<ConverterReturned, DetailedError> {
+        let client = HttpExecutorClient::new(Duration::from_millis(timeout)).unwrap();
+        let executor = Executor { protocol: Protocol::Http, url: url.to_string(), settings: "".to_string() };
+        System::new("test").block_on(async move { client.execute(&executor, &para()).await })
+    }
+
+    #[test]
+    fn returned_test() {
+        let no_wait = Duration::from_millis(0);
+        let (url, _) = serve("/convert", vec![(200, r#""None""#, no_wait), (200, r#"{"Delay":30}"#, no_wait), (200, r#"{"Delay":0}"#, no_wait), (200, "<html>", no_wait)]);
+        assert_eq!(execute(&url, 3000), Ok(ConverterReturned::None));
+        assert_eq!(execute(&url, 3000), Ok(ConverterReturned::Delay(30)));
+        assert_eq!(execute(&url, 3000).unwrap_err().kind(), ErrorKind::Logical);
+        assert_eq!(execute(&url, 3000).unwrap_err().is_retryable(), false);
+    }
+
+    #[test]
+    fn error_response_test() {
+        let no_wait = Duration::from_millis(0);
+        let (url, _) = serve("/convert", vec![(409, r#"{"status":409,"detail":"dup"}"#, no_wait), (503, "busy", no_wait), (500, "oops", no_wait)]);
+        let e = execute(&url, 3000).unwrap_err();
+        assert_eq!(e.code, ErrorCode::DaoDuplicated);
+        assert_eq!(e.task_id, Some("t1".to_string()));
+        let e = execute(&url, 3000).unwrap_err();
+        assert_eq!(e.code, ErrorCode::HttpServer);
+        assert_eq!(e.message, "busy");
+        assert_eq!(e.is_retryable(), true);
+        let e = execute(&url, 3000).unwrap_err();
+        assert_eq!(e.code, ErrorCode::HttpServer);
+        assert_eq!(e.is_retryable(), true);
+    }
+
+    #[test]
+    fn transport_error_test() {
+        let (url, _) = serve("/convert", vec![(200, r#""None""#, Duration::from_millis(1000))]);
+        let e = execute(&url, 100).unwrap_err();
+        assert_eq!(e.code, ErrorCode::Timeout);
+        // nobody listen
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/convert", listener.local_addr().unwrap());
+        drop(listener);
+        let e = execute(&url, 1000).unwrap_err();
+        assert_eq!(e.code, ErrorCode::Connect);
+    }
+
+    #[test]
+    fn url_test() {
+        assert_eq!(execute("https://localhost/a", 100).is_err(), true);
+        let executor = Executor::for_local("a:b");
+        assert_eq!(check_url(&executor).is_err(), true);
+        let executor = Executor { protocol: Protocol::Https, url: "HTTPS://a/b".to_string(), settings: "".to_string() };
+        assert_eq!(check_url(&executor), Ok(()));
+    }
+}
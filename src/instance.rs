@@ -1,14 +1,16 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
-use std::iter::Iterator;
 use std::ops::{Deref, DerefMut};
 
 use chrono::prelude::*;
 use futures::Future;
 use itertools::Itertools;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
 
-use crate::{FromInstance, generate_id, ID, is_default, KeyCondition, MetaType, NatureError, Result, SEPARATOR_INS_KEY, SEPARATOR_META, TargetState};
+use crate::{FromInstance, ID, id_generator, IdGenerator, is_default, KeyCondition, MetaType, NatureError, Result, SEPARATOR_INS_KEY, SEPARATOR_META, snowflake_generator, TargetState};
 use crate::converter::DynamicConverter;
 
 use super::Meta;
@@ -21,6 +23,7 @@ pub static CONTEXT_LOOP_NEXT: &str = "loop.next";
 pub static CONTEXT_LOOP_ID: &str = "loop.id";
 pub static CONTEXT_LOOP_TASK: &str = "loop.task";
 pub static CONTEXT_LOOP_FINISHED: &str = "loop.finished";
+pub static CONTEXT_LOOP_LEN: &str = "loop.len";
 
 pub static CONTEXT_DYNAMIC_PARA: &str = "para.dynamic";
 
@@ -60,10 +63,36 @@ impl Instance {
         })
     }
 
+    /// create an `Instance` for `meta_str` with the `value` serialized as content,
+    /// `meta_str`'s format : [MetaType]:[key]:[version]
+    pub fn from_value<T: Serialize>(meta_str: &str, value: &T) -> Result<Self> {
+        let meta = Meta::from_string(meta_str)?;
+        let mut rtn = Instance::default();
+        rtn.data.meta = meta.meta_string();
+        rtn.data.set_content(value)?;
+        Ok(rtn)
+    }
+
     pub fn revise(&mut self) -> Result<&mut Self> {
         self.create_time = Local::now().timestamp_millis();
         if self.para.is_empty() && self.id == 0 {
-            self.id = generate_id(&self.data)?;
+            self.id = id_generator()?.generate(&self.data)?;
+        }
+        Ok(self)
+    }
+
+    /// like `revise`, but use time-ordered id if `MetaSetting::time_ordered_id` is set for `meta`
+    pub fn revise_by(&mut self, meta: &Meta) -> Result<&mut Self> {
+        let time_ordered = match meta.get_setting() {
+            Some(setting) => setting.time_ordered_id,
+            None => false
+        };
+        if !time_ordered {
+            return self.revise();
+        }
+        self.create_time = Local::now().timestamp_millis();
+        if self.para.is_empty() && self.id == 0 {
+            self.id = snowflake_generator()?.generate(&self.data)?;
         }
         Ok(self)
     }
@@ -96,6 +125,37 @@ impl Instance {
         }
     }
 
+    /// Iterate the upstream `Instance`s from the nearest to the root.
+    /// `dao` is used to load the `Instance` for each `from`.
+    /// The iteration stops after an error returned, such as not found or cycle found.
+    pub fn lineage<F>(&self, dao: F) -> Lineage<F>
+        where F: FnMut(KeyCondition) -> Result<Option<Instance>>
+    {
+        Lineage {
+            walk: LineageWalk::new(self),
+            dao,
+        }
+    }
+
+    /// The async version of `lineage`, at most `max_depth` upstream `Instance`s will be loaded.
+    /// `dao` is used to load the upstream instance, just like `get_master`
+    pub async fn lineage_async<F, D>(&self, max_depth: usize, dao: D) -> Result<Vec<Instance>>
+        where F: Future<Output=Result<Option<Instance>>>,
+              D: Fn(KeyCondition) -> F
+    {
+        let mut walk = LineageWalk::new(self);
+        let mut rtn: Vec<Instance> = vec![];
+        while rtn.len() < max_depth {
+            let from = match walk.next_from() {
+                None => break,
+                Some(from) => from?
+            };
+            let loaded = dao(KeyCondition::from(&from)).await;
+            rtn.push(walk.accept(&from, loaded)?);
+        }
+        Ok(rtn)
+    }
+
     pub fn get_key(&self) -> String {
         let sep: &str = &*SEPARATOR_INS_KEY;
         format!("{}{}{:x}{}{}{}{}", self.meta, sep, self.id, sep, self.para, sep, self.state_version)
@@ -140,11 +200,60 @@ impl Into<KeyCondition> for Instance {
     }
 }
 
-impl Iterator for Instance {
-    type Item = Instance;
+/// The state of walking along the `from` chain, shared by `Instance::lineage` and `Instance::lineage_async`
+struct LineageWalk {
+    next: Option<FromInstance>,
+    visited: HashSet<String>,
+}
+
+impl LineageWalk {
+    fn new(ins: &Instance) -> Self {
+        let mut visited = HashSet::new();
+        visited.insert(FromInstance::from(ins).to_string());
+        LineageWalk { next: ins.from.clone(), visited }
+    }
+
+    /// the upstream to be loaded, `None` if reached the root, the walk stops after an error returned.
+    fn next_from(&mut self) -> Option<Result<FromInstance>> {
+        let from = self.next.take()?;
+        if !self.visited.insert(from.to_string()) {
+            let msg = format!("cycle found in lineage at: {}", from.to_string());
+            warn!("{}", &msg);
+            return Some(Err(NatureError::VerifyError(msg)));
+        }
+        Some(Ok(from))
+    }
+
+    /// accept the loaded upstream for `from`
+    fn accept(&mut self, from: &FromInstance, loaded: Result<Option<Instance>>) -> Result<Instance> {
+        match loaded? {
+            Some(ins) => {
+                self.next = ins.from.clone();
+                Ok(ins)
+            }
+            None => Err(NatureError::VerifyError(format!("upstream instance not found: {}", from.to_string())))
+        }
+    }
+}
+
+/// Iterate the upstream `Instance`s along the `from` chain, see `Instance::lineage`
+pub struct Lineage<F> {
+    walk: LineageWalk,
+    dao: F,
+}
+
+impl<F> Iterator for Lineage<F>
+    where F: FnMut(KeyCondition) -> Result<Option<Instance>>
+{
+    type Item = Result<Instance>;
+
     fn next(&mut self) -> Option<Self::Item> {
-        let rtn: &Instance = self;
-        Some(rtn.clone())
+        let from = match self.walk.next_from()? {
+            Ok(from) => from,
+            Err(e) => return Some(Err(e))
+        };
+        let loaded = (self.dao)(KeyCondition::from(&from));
+        Some(self.walk.accept(&from, loaded))
     }
 }
 
@@ -199,6 +308,55 @@ impl Hash for BizObject {
 }
 
 impl BizObject {
+    /// context entries ordered by key
+    pub fn context_entries(&self) -> impl Iterator<Item=(&String, &String)> {
+        self.context.iter().sorted()
+    }
+
+    pub fn get_content<T: DeserializeOwned>(&self) -> Result<T> {
+        match serde_json::from_str(&self.content) {
+            Ok(rtn) => Ok(rtn),
+            Err(e) => Err(NatureError::VerifyError(format!("content of [{}] can't be deserialized: {}", self.meta, e)))
+        }
+    }
+
+    pub fn set_content<T: Serialize>(&mut self, value: &T) -> Result<()> {
+        match serde_json::to_string(value) {
+            Ok(content) => {
+                self.content = content;
+                Ok(())
+            }
+            Err(e) => Err(NatureError::VerifyError(format!("content of [{}] can't be serialized: {}", self.meta, e)))
+        }
+    }
+
+    /// A context value which is not a json will be treated as a json string, so that `String` can be get directly.
+    pub fn get_context<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let value = match self.context.get(key) {
+            Some(value) => value,
+            None => return Ok(None)
+        };
+        let rtn = match serde_json::from_str(value) {
+            Ok(rtn) => Ok(rtn),
+            Err(_) => serde_json::from_value(Value::String(value.to_string()))
+        };
+        match rtn {
+            Ok(rtn) => Ok(Some(rtn)),
+            Err(e) => Err(NatureError::VerifyError(format!("context [{}] of [{}] can't be deserialized: {}", key, self.meta, e)))
+        }
+    }
+
+    /// A json string value will be saved without quotation marks.
+    pub fn set_context<T: Serialize>(&mut self, key: &str, value: &T) -> Result<()> {
+        let value = match serde_json::to_value(value) {
+            Ok(Value::String(s)) => s,
+            Ok(v) => v.to_string(),
+            Err(e) => return Err(NatureError::VerifyError(format!("context [{}] of [{}] can't be serialized: {}", key, self.meta, e)))
+        };
+        self.context.insert(key.to_string(), value);
+        Ok(())
+    }
+
     pub fn modify_state(&mut self, add_and_delete: &TargetState, meta: &Meta) {
         // delete first
         if let Some(x) = &add_and_delete.remove {
@@ -269,7 +427,108 @@ mod test {
         let rtn = serde_json::to_string(&order).unwrap();
         assert_eq!(rtn, r#"{"data":{"meta":"B:sale/order:1","content":"my order detail"}}"#);
     }
-}
 
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Order {
+        id: u32,
+        items: Vec<String>,
+    }
 
+    #[test]
+    fn typed_content_test() {
+        let order = Order { id: 1, items: vec!["a".to_string()] };
+        let mut ins = Instance::from_value("B:sale/order:2", &order).unwrap();
+        assert_eq!(ins.meta, "B:sale/order:2");
+        assert_eq!(ins.content, r#"{"id":1,"items":["a"]}"#);
+        assert_eq!(ins.get_content::<Order>().unwrap(), order);
+        ins.set_content(&Order { id: 2, items: vec![] }).unwrap();
+        assert_eq!(ins.get_content::<Order>().unwrap().id, 2);
+        ins.content = "bad".to_string();
+        assert_eq!(ins.get_content::<Order>().is_err(), true);
+        assert_eq!(Instance::from_value("B:sale/order", &order).is_err(), true);
+    }
 
+    #[test]
+    fn typed_context_test() {
+        let mut ins = Instance::new("sale/order").unwrap();
+        assert_eq!(ins.get_context::<u32>("count").unwrap(), None);
+        ins.set_context("count", &3).unwrap();
+        ins.set_context("shop", &"s1").unwrap();
+        ins.set_context("tags", &vec!["a", "b"]).unwrap();
+        assert_eq!(ins.context["count"], "3");
+        assert_eq!(ins.context["shop"], "s1");
+        assert_eq!(ins.context["tags"], r#"["a","b"]"#);
+        assert_eq!(ins.get_context::<u32>("count").unwrap(), Some(3));
+        assert_eq!(ins.get_context::<String>("shop").unwrap(), Some("s1".to_string()));
+        assert_eq!(ins.get_context::<Vec<String>>("tags").unwrap().unwrap().len(), 2);
+        assert_eq!(ins.get_context::<u32>("shop").is_err(), true);
+    }
+
+    #[test]
+    fn context_entries_test() {
+        let mut ins = Instance::new("sale/order").unwrap();
+        ins.context.insert("b".to_string(), "2".to_string());
+        ins.context.insert("a".to_string(), "1".to_string());
+        let rtn: Vec<(&String, &String)> = ins.context_entries().collect();
+        assert_eq!(rtn, vec![(&"a".to_string(), &"1".to_string()), (&"b".to_string(), &"2".to_string())]);
+    }
+
+    fn chain() -> HashMap<String, Instance> {
+        let mut rtn: HashMap<String, Instance> = HashMap::new();
+        let mut upstream: Option<FromInstance> = None;
+        for (id, meta) in ["root", "middle", "last"].iter().enumerate() {
+            let mut ins = Instance::new(meta).unwrap();
+            ins.id = id as ID + 1;
+            ins.from = upstream;
+            upstream = Some(FromInstance::from(&ins));
+            rtn.insert(KeyCondition::from(&ins).get_key(), ins);
+        }
+        rtn
+    }
+
+    #[test]
+    fn lineage_test() {
+        let map = chain();
+        let last = map.values().find(|one| one.meta == "B:last:1").unwrap();
+        let rtn: Vec<String> = last.lineage(|c: KeyCondition| Ok(map.get(&c.get_key()).cloned()))
+            .map(|one| one.unwrap().meta.to_string()).collect();
+        assert_eq!(rtn, vec!["B:middle:1", "B:root:1"]);
+
+        // not found
+        let rtn: Vec<Result<Instance>> = last.lineage(|_: KeyCondition| Ok(None)).collect();
+        assert_eq!(rtn.len(), 1);
+        assert_eq!(rtn[0].is_err(), true);
+    }
+
+    #[test]
+    fn lineage_cycle_test() {
+        let mut ins = Instance::new("self").unwrap();
+        ins.id = 1;
+        ins.from = Some(FromInstance::from(&ins));
+        let copy = ins.clone();
+        let rtn: Vec<Result<Instance>> = ins.lineage(|_: KeyCondition| Ok(Some(copy.clone()))).collect();
+        assert_eq!(rtn.len(), 1);
+        assert_eq!(rtn[0], Err(NatureError::VerifyError("cycle found in lineage at: B:self:1|1||0".to_string())));
+    }
+
+    #[test]
+    fn revise_by_test() {
+        let mut event = Meta::new("sale/click", 1, MetaType::Business).unwrap();
+        event.set_setting(r#"{"time_ordered_id":true}"#).unwrap();
+        let ins = Instance::new("sale/click").unwrap();
+        let a = ins.clone().revise_by(&event).unwrap().id;
+        let b = ins.clone().revise_by(&event).unwrap().id;
+        assert_ne!(a, b);
+        assert_eq!(a < b, true);
+
+        let state = Meta::new("sale/click", 1, MetaType::Business).unwrap();
+        let a = ins.clone().revise_by(&state).unwrap().id;
+        let b = ins.clone().revise_by(&state).unwrap().id;
+        assert_eq!(a, b);
+        assert_eq!(a, ins.clone().revise().unwrap().id);
+
+        let mut with_para = ins.clone();
+        with_para.para = "p".to_string();
+        assert_eq!(with_para.revise_by(&event).unwrap().id, 0);
+    }
+}
@@ -0,0 +1,184 @@
+use serde::Serialize;
+
+use crate::{BizObject, FromInstance, ID, Instance, Meta, MetaType, NatureError, Result, SEPARATOR_INS_KEY};
+
+/// Build an `Instance` for any `Meta`, the result is checked against the `Meta` and revised.
+pub struct InstanceBuilder {
+    meta: Meta,
+    id: ID,
+    data: BizObject,
+    /// the first error occurred while setting, it will be returned by `build`
+    error: Option<NatureError>,
+}
+
+impl InstanceBuilder {
+    pub fn new(meta: &Meta) -> Self {
+        InstanceBuilder {
+            meta: meta.clone(),
+            id: 0,
+            data: BizObject {
+                meta: meta.meta_string(),
+                ..Default::default()
+            },
+            error: None,
+        }
+    }
+
+    pub fn id(mut self, id: ID) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn content(mut self, content: &str) -> Self {
+        self.data.content = content.to_string();
+        self
+    }
+
+    /// serialize the `value` to json as content
+    pub fn content_value<T: Serialize>(mut self, value: &T) -> Self {
+        if let Err(e) = self.data.set_content(value) {
+            self.error.get_or_insert(e);
+        }
+        self
+    }
+
+    pub fn context(mut self, key: &str, value: &str) -> Self {
+        self.data.context.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn sys_context(mut self, key: &str, value: &str) -> Self {
+        self.data.sys_context.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn state(mut self, state: &str) -> Self {
+        self.data.states.insert(state.to_string());
+        self
+    }
+
+    pub fn states(mut self, states: &[&str]) -> Self {
+        states.iter().for_each(|one| { self.data.states.insert(one.to_string()); });
+        self
+    }
+
+    pub fn para(mut self, para: &str) -> Self {
+        self.data.para = para.to_string();
+        self
+    }
+
+    pub fn from(mut self, from: FromInstance) -> Self {
+        self.data.from = Some(from);
+        self
+    }
+
+    pub fn build(self) -> Result<Instance> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        let meta_str = self.meta.meta_string();
+        if self.meta.get_meta_type() == MetaType::Null {
+            return Err(NatureError::VerifyError(format!("can't build instance for [{}]", meta_str)));
+        }
+        if !self.data.states.is_empty() {
+            let states: Vec<String> = self.data.states.iter().cloned().collect();
+            let (_, mutex) = self.meta.check_state(&states)?;
+            if let Some((a, b)) = mutex.first() {
+                let msg = format!("[{}] and [{}] are mutex in meta: {}", a, b, meta_str);
+                return Err(NatureError::VerifyError(msg));
+            }
+        }
+        self.data.sys().verify()?;
+        if self.data.para.contains(&*SEPARATOR_INS_KEY) {
+            let msg = format!("para [{}] can't contain [{}]", self.data.para, *SEPARATOR_INS_KEY);
+            return Err(NatureError::VerifyError(msg));
+        }
+        let mut rtn = Instance {
+            id: self.id,
+            data: self.data,
+            create_time: 0,
+        };
+        rtn.revise_by(&self.meta)?;
+        Ok(rtn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::State;
+
+    use super::*;
+
+    fn state_meta() -> Meta {
+        let mut meta = Meta::new("sale/order", 3, MetaType::Business).unwrap();
+        let (ss, _) = State::string_to_states("new|paid,urgent").unwrap();
+        meta.set_states(Some(ss)).unwrap();
+        meta
+    }
+
+    #[test]
+    fn build_test() {
+        let meta = Meta::new("sale/report", 2, MetaType::System).unwrap();
+        let ins = InstanceBuilder::new(&meta)
+            .content_value(&vec![1, 2])
+            .context("shop", "s1")
+            .sys_context("target.id", "1")
+            .from(FromInstance::default())
+            .build().unwrap();
+        assert_eq!(ins.meta, "S:sale/report:2");
+        assert_eq!(ins.content, "[1,2]");
+        assert_eq!(ins.context["shop"], "s1");
+        assert_eq!(ins.sys_context["target.id"], "1");
+        assert_eq!(ins.from.is_some(), true);
+        assert_eq!(ins.id != 0, true);
+        assert_eq!(ins.create_time > 0, true);
+    }
+
+    #[test]
+    fn id_and_para() {
+        let meta = Meta::new("sale/report", 1, MetaType::Business).unwrap();
+        let ins = InstanceBuilder::new(&meta).para("a/b").build().unwrap();
+        assert_eq!(ins.id, 0);
+        assert_eq!(ins.para, "a/b");
+        let ins = InstanceBuilder::new(&meta).id(5).build().unwrap();
+        assert_eq!(ins.id, 5);
+        let rtn = InstanceBuilder::new(&meta).para("a|b").build();
+        assert_eq!(rtn, Err(NatureError::VerifyError("para [a|b] can't contain [|]".to_string())));
+    }
+
+    #[test]
+    fn states_test() {
+        let meta = state_meta();
+        let ins = InstanceBuilder::new(&meta).states(&["paid", "urgent"]).build().unwrap();
+        assert_eq!(ins.states.len(), 2);
+        let rtn = InstanceBuilder::new(&meta).state("shipped").build();
+        assert_eq!(rtn, Err(NatureError::VerifyError("[shipped] does not defined in meta: B:sale/order:3".to_string())));
+        let rtn = InstanceBuilder::new(&meta).states(&["new", "paid"]).build();
+        assert_eq!(rtn.is_err(), true);
+        let rtn = InstanceBuilder::new(&Meta::new("a", 1, MetaType::Business).unwrap()).state("new").build();
+        assert_eq!(rtn, Err(NatureError::VerifyError("[B:a:1] is not a state meta".to_string())));
+    }
+
+    #[test]
+    fn sys_context_test() {
+        let meta = Meta::new("sale/report", 1, MetaType::Business).unwrap();
+        let rtn = InstanceBuilder::new(&meta).sys_context("target.idd", "1").build();
+        assert_eq!(rtn, Err(NatureError::VerifyError("unknown sys_context key: target.idd".to_string())));
+        let rtn = InstanceBuilder::new(&meta).sys_context("loop.finished", "1").build();
+        assert_eq!(rtn.is_err(), true);
+    }
+
+    #[test]
+    fn custom_sys_context_test() {
+        let meta = Meta::new("sale/report", 1, MetaType::Business).unwrap();
+        let ins = InstanceBuilder::new(&meta).sys_context("trace.source", "app1").build().unwrap();
+        assert_eq!(ins.sys_context["trace.source"], "app1");
+    }
+
+    #[test]
+    fn null_meta() {
+        let meta = Meta::new("", 1, MetaType::Null).unwrap();
+        let rtn = InstanceBuilder::new(&meta).build();
+        assert_eq!(rtn, Err(NatureError::VerifyError("can't build instance for [N::1]".to_string())));
+    }
+}
@@ -8,17 +8,35 @@ extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
 
+pub use builtin_executor::*;
 pub use callback::*;
+pub use codec::*;
+pub use delayed_task::*;
 pub use converter::*;
 pub use error::*;
+pub use executor_setting::*;
 pub use from_instance::*;
+pub use http_executor::*;
 pub use instance::*;
+pub use instance_builder::*;
 pub use instance_para::*;
+pub use loop_context::*;
+pub use loop_coordinator::*;
+pub use local_executor::*;
+pub use meta_registry::*;
 pub use meta_setting::*;
+pub use meta_version::*;
 pub use meta_type::*;
+pub use para_template::*;
+pub use problem::*;
+pub use protocol_registry::*;
+pub use provenance::*;
 pub use query::*;
+pub use schema::*;
 pub use settings::*;
 pub use state::*;
+pub use state_parser::*;
+pub use sys_context::*;
 pub use target_state::*;
 pub use util::*;
 
@@ -27,17 +45,38 @@ pub use crate::meta::*;
 mod converter;
 mod error;
 mod instance;
+mod instance_builder;
 mod meta;
 mod meta_type;
 mod meta_setting;
+mod meta_registry;
+mod meta_version;
 mod util;
 mod state;
+mod state_parser;
+mod state_diagram;
 mod query;
+mod provenance;
+mod schema;
 mod target_state;
 mod callback;
 mod from_instance;
 mod settings;
 mod instance_para;
+mod codec;
+mod problem;
+mod http_executor;
+mod local_executor;
+mod builtin_executor;
+mod executor_setting;
+mod protocol_registry;
+mod delayed_task;
+mod loop_context;
+mod loop_coordinator;
+mod sys_context;
+mod para_template;
+#[cfg(test)]
+mod test_server;
 
 
 pub type Result<T> = std::result::Result<T, NatureError>;
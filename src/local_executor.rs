@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::sync::{Arc, Mutex, RwLock};
+
+use libloading::Library;
+
+use crate::{ConverterParameter, ConverterReturned, Executor, NatureError, Protocol, Result};
+
+/// The signature of the converter for `Protocol::LocalRust`,
+/// a function in a cdylib should be declared with `#[no_mangle]`.
+/// It's not a stable ABI, so the cdylib must be built by the same rustc and the same version of nature_common
+/// as the one which loads it, or the call is undefined behaviour.
+pub type LocalConverter = fn(&ConverterParameter) -> ConverterReturned;
+
+lazy_static! {
+    /// registered and loaded converters, the key is `Executor::url`
+    static ref LOCAL_CONVERTERS: RwLock<HashMap<String, LocalConverter>> = RwLock::new(HashMap::new());
+    /// the loaded libraries must be kept, or the converters from them would be invalid.
+    static ref LIBRARIES: Mutex<HashMap<String, Arc<Library>>> = Mutex::new(HashMap::new());
+}
+
+/// split `url` into library and function, the format is `lib:function`
+fn split_url(url: &str) -> Result<(&str, &str)> {
+    match url.rfind(':') {
+        Some(idx) if idx > 0 && idx < url.len() - 1 => Ok((&url[..idx], &url[idx + 1..])),
+        _ => Err(NatureError::VerifyError(format!("url of local executor should be lib:function, but get: {}", url)))
+    }
+}
+
+/// `lib` is used as the file name if it contains `.` or a path separator,
+/// else it's decorated for the platform, e.g. `converter` → `libconverter.so`
+fn library_file(lib: &str) -> String {
+    if lib.contains('.') || lib.contains('/') || lib.contains('\\') {
+        lib.to_string()
+    } else {
+        format!("{}{}{}", DLL_PREFIX, lib, DLL_SUFFIX)
+    }
+}
+
+/// Register a converter implemented in the application, it has a higher priority than the dynamic one.
+/// It's the only safe way to provide a converter.
+pub fn register_local_converter(url: &str, converter: LocalConverter) -> Result<()> {
+    split_url(url)?;
+    LOCAL_CONVERTERS.write().unwrap().insert(url.to_string(), converter);
+    Ok(())
+}
+
+/// Find the converter for `url` which is registered or loaded by `load_local_converter`.
+pub fn local_converter(url: &str) -> Result<LocalConverter> {
+    match LOCAL_CONVERTERS.read().unwrap().get(url) {
+        Some(converter) => Ok(*converter),
+        None => Err(NatureError::VerifyError(format!("local converter is not registered or loaded: {}", url)))
+    }
+}
+
+/// Find the registered converter for `url`, or load it from the cdylib, the loaded one will be cached.
+///
+/// # Safety
+///
+/// The symbol is called as a `LocalConverter` without any check, so the function named by `url` must be
+/// declared with exactly that signature, and the cdylib must be built by the same rustc and the same version
+/// of nature_common as this one. The initialization of the library is run when it's loaded.
+pub unsafe fn load_local_converter(url: &str) -> Result<LocalConverter> {
+    if let Ok(converter) = local_converter(url) {
+        return Ok(converter);
+    }
+    let (lib, fun) = split_url(url)?;
+    let file = library_file(lib);
+    let library = load_library(&file)?;
+    let symbol = library.get::<LocalConverter>(fun.as_bytes());
+    let converter = match symbol {
+        Ok(symbol) => *symbol,
+        Err(e) => {
+            let msg = format!("function [{}] not found in library [{}]: {}", fun, file, e);
+            warn!("{}", &msg);
+            return Err(NatureError::LogicalError(msg));
+        }
+    };
+    LOCAL_CONVERTERS.write().unwrap().insert(url.to_string(), converter);
+    Ok(converter)
+}
+
+/// the loaded library is cached
+fn load_library(file: &str) -> Result<Arc<Library>> {
+    let mut libraries = LIBRARIES.lock().unwrap();
+    if let Some(library) = libraries.get(file) {
+        return Ok(library.clone());
+    }
+    let library = match Library::new(file) {
+        Ok(library) => Arc::new(library),
+        Err(e) => {
+            let msg = format!("can't load library [{}] for local executor: {}", file, e);
+            warn!("{}", &msg);
+            return Err(NatureError::LogicalError(msg));
+        }
+    };
+    libraries.insert(file.to_string(), library.clone());
+    Ok(library)
+}
+
+impl Executor {
+    /// call the converter of `Protocol::LocalRust`, it must be registered or loaded by `load_local_converter` first.
+    pub fn call_local(&self, para: &ConverterParameter) -> Result<ConverterReturned> {
+        if self.protocol != Protocol::LocalRust {
+            return Err(NatureError::VerifyError(format!("{:?} is not a local executor", self.protocol)));
+        }
+        let converter = local_converter(&self.url)?;
+        Ok(converter(para))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Instance;
+
+    use super::*;
+
+    fn echo(para: &ConverterParameter) -> ConverterReturned {
+        ConverterReturned::Instances(vec![para.from.clone()])
+    }
+
+    fn para() -> ConverterParameter {
+        ConverterParameter {
+            from: Instance::new("a").unwrap(),
+            last_state: None,
+            task_id: "".to_string(),
+            master: None,
+            cfg: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn static_test() {
+        register_local_converter("app:echo", echo).unwrap();
+        let rtn = Executor::for_local("app:echo").call_local(&para()).unwrap();
+        assert_eq!(rtn, ConverterReturned::Instances(vec![para().from]));
+        assert_eq!(register_local_converter("echo", echo).is_err(), true);
+    }
+
+    #[test]
+    fn url_test() {
+        assert_eq!(split_url("a:b"), Ok(("a", "b")));
+        assert_eq!(split_url("c:/lib/a.so:b"), Ok(("c:/lib/a.so", "b")));
+        assert_eq!(split_url(":b").is_err(), true);
+        assert_eq!(split_url("a:").is_err(), true);
+        assert_eq!(library_file("/lib/a.so"), "/lib/a.so");
+        assert_eq!(library_file("a"), format!("{}a{}", DLL_PREFIX, DLL_SUFFIX));
+        let exe = Executor::new_auto();
+        assert_eq!(exe.call_local(&para()), Err(NatureError::VerifyError("Auto is not a local executor".to_string())));
+    }
+
+    #[test]
+    fn missing_test() {
+        let rtn = local_converter("not_exists_lib:f");
+        assert_eq!(rtn, Err(NatureError::VerifyError("local converter is not registered or loaded: not_exists_lib:f".to_string())));
+        let rtn = unsafe { load_local_converter("not_exists_lib:f") };
+        assert_eq!(rtn.unwrap_err().to_string().contains("can't load library"), true);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn dynamic_test() {
+        // `cos` is not a `LocalConverter`, so only probe it, and never put it into `LOCAL_CONVERTERS`
+        let library = load_library("libm.so.6").unwrap();
+        assert_eq!(unsafe { library.get::<*const u8>(b"cos") }.is_ok(), true);
+        assert_eq!(Arc::ptr_eq(&library, &load_library("libm.so.6").unwrap()), true);
+        let rtn = unsafe { load_local_converter("libm.so.6:no_such_function") };
+        assert_eq!(rtn.unwrap_err().to_string().contains("function [no_such_function] not found in library [libm.so.6]"), true);
+        assert_eq!(LOCAL_CONVERTERS.read().unwrap().keys().any(|k| k.starts_with("libm.so.6:")), false);
+    }
+}
@@ -1,5 +1,8 @@
-#[derive(Serialize, Deserialize, Debug)]
+/// The cursor of a loop for `MetaType::Loop`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct LoopContext {
+    /// given by the converter to fetch the next batch, empty for the first batch
     pub next: String,
+    /// how many batches have been processed
     pub len: usize,
-}
\ No newline at end of file
+}
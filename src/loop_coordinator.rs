@@ -0,0 +1,338 @@
+use std::hash::Hasher;
+
+use futures::Future;
+use siphasher::sip::SipHasher24;
+
+use crate::{CONTEXT_LOOP_FINISHED, CONTEXT_LOOP_ID, CONTEXT_LOOP_NEXT, CONTEXT_LOOP_TASK, ConverterParameter, ConverterReturned, FromInstance, Instance, LoopContext, Meta, MetaSetting, MetaType, NatureError, Result};
+
+/// Check the `Meta` can be used as a loop target:
+/// it should be `MetaType::Loop` with `multi_meta`, and `only_one` requires exactly one `multi_meta`.
+pub fn check_loop_meta(meta: &Meta) -> Result<MetaSetting> {
+    let meta_str = meta.meta_string();
+    if meta.get_meta_type() != MetaType::Loop {
+        return Err(NatureError::VerifyError(format!("[{}] is not a loop meta", meta_str)));
+    }
+    let setting = match meta.get_setting() {
+        Some(setting) if !setting.multi_meta.is_empty() => setting,
+        _ => return Err(NatureError::VerifyError(format!("multi_meta of loop meta [{}] should not be empty", meta_str)))
+    };
+    if setting.only_one && setting.multi_meta.len() != 1 {
+        let msg = format!("only_one of loop meta [{}] requires one multi_meta, but get {}", meta_str, setting.multi_meta.len());
+        return Err(NatureError::VerifyError(msg));
+    }
+    Ok(setting)
+}
+
+/// Drive a batch-producing converter for `MetaType::Loop`.
+///
+/// The converter returns a batch of instances each time, and puts the cursor for the next batch in the
+/// sys_context `loop.next` of the first instance; no `loop.next` or `loop.finished` being "true" ends the loop.
+/// For `only_one`, the converter returns the accumulated instance each time, which will be given back
+/// by `ConverterParameter::last_state`, and it will be emitted only when the loop finished.
+///
+/// The instances of the last batch carry the sys_context `loop.finished`. If the last batch is empty,
+/// e.g. `ConverterReturned::None`, no instance carries it except the accumulated one of `only_one`,
+/// so use `is_finished` to know the end of the loop.
+#[derive(Debug, Clone)]
+pub struct LoopCoordinator {
+    setting: MetaSetting,
+    upstream: Instance,
+    task_id: String,
+    loop_id: String,
+    context: LoopContext,
+    finished: bool,
+    /// the accumulated instance for `only_one`
+    last: Option<Instance>,
+}
+
+impl LoopCoordinator {
+    pub fn new(meta: &Meta, upstream: &Instance, task_id: &str) -> Result<Self> {
+        Ok(LoopCoordinator {
+            setting: check_loop_meta(meta)?,
+            upstream: upstream.clone(),
+            task_id: task_id.to_string(),
+            loop_id: loop_id(upstream, task_id),
+            context: LoopContext::default(),
+            finished: false,
+            last: None,
+        })
+    }
+
+    pub fn loop_id(&self) -> &str {
+        &self.loop_id
+    }
+
+    pub fn context(&self) -> &LoopContext {
+        &self.context
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// the parameter for the converter to produce the next batch
+    pub fn parameter(&self) -> ConverterParameter {
+        let mut from = self.upstream.clone();
+        from.sys_context.insert(CONTEXT_LOOP_ID.to_string(), self.loop_id.to_string());
+        from.sys_context.insert(CONTEXT_LOOP_TASK.to_string(), self.task_id.to_string());
+        from.sys_mut().set_loop_context(&self.context);
+        ConverterParameter {
+            from,
+            last_state: self.last.clone(),
+            task_id: self.task_id.to_string(),
+            master: None,
+            cfg: "".to_string(),
+        }
+    }
+
+    /// Accept the result of one batch, return the instances which should be saved now.
+    /// A rejected batch changes nothing, so it can be retried.
+    pub fn apply(&mut self, returned: ConverterReturned) -> Result<Vec<Instance>> {
+        if self.finished {
+            return Err(NatureError::LogicalError(format!("loop [{}] has finished", self.loop_id)));
+        }
+        let mut instances = match returned {
+            ConverterReturned::Instances(instances) => instances,
+            ConverterReturned::None => vec![],
+            ConverterReturned::LogicalError(msg) => return Err(NatureError::LogicalError(msg)),
+            ConverterReturned::EnvError(msg) => return Err(NatureError::EnvironmentError(msg)),
+            other => return Err(NatureError::LogicalError(format!("loop converter can't return: {:?}", other)))
+        };
+        let next = take_next(&mut instances);
+        if let Some(next) = &next {
+            if *next == self.context.next {
+                let msg = format!("loop [{}] did not move forward, the next is still: {}", self.loop_id, next);
+                warn!("{}", &msg);
+                return Err(NatureError::LogicalError(msg));
+            }
+        }
+        if self.setting.only_one && instances.len() > 1 {
+            let msg = format!("only_one loop [{}] should return one instance each time, but get {}", self.loop_id, instances.len());
+            return Err(NatureError::LogicalError(msg));
+        }
+        self.setting.check_multi_meta(&mut instances, &FromInstance::from(&self.upstream))?;
+        // all checked, the state can be changed now
+        for one in instances.iter_mut() {
+            one.sys_context.insert(CONTEXT_LOOP_ID.to_string(), self.loop_id.to_string());
+            one.sys_context.insert(CONTEXT_LOOP_TASK.to_string(), self.task_id.to_string());
+        }
+        self.context.len += 1;
+        match next {
+            Some(next) => self.context.next = next,
+            None => self.finished = true
+        }
+        if !self.setting.only_one {
+            if self.finished {
+                instances.iter_mut().for_each(mark_finished);
+            }
+            return Ok(instances);
+        }
+        if let Some(one) = instances.pop() {
+            self.last = Some(one);
+        }
+        match (self.finished, &mut self.last) {
+            (true, Some(last)) => {
+                mark_finished(last);
+                Ok(vec![last.clone()])
+            }
+            _ => Ok(vec![])
+        }
+    }
+
+    /// Call the `converter` until the loop finished, return all the emitted instances.
+    /// `max_batches` is used to avoid endless loop.
+    pub async fn run<F, Fut>(&mut self, max_batches: usize, mut converter: F) -> Result<Vec<Instance>>
+        where F: FnMut(ConverterParameter) -> Fut,
+              Fut: Future<Output=ConverterReturned>
+    {
+        let mut rtn: Vec<Instance> = vec![];
+        while !self.finished {
+            if self.context.len >= max_batches {
+                return Err(NatureError::LogicalError(format!("loop [{}] exceeded {} batches", self.loop_id, max_batches)));
+            }
+            let returned = converter(self.parameter()).await;
+            rtn.append(&mut self.apply(returned)?);
+        }
+        Ok(rtn)
+    }
+}
+
+/// take the cursor out from the first instance
+fn take_next(instances: &mut [Instance]) -> Option<String> {
+    let first = instances.first_mut()?;
+    let next = first.sys_context.remove(CONTEXT_LOOP_NEXT);
+    let finished = first.sys_context.remove(CONTEXT_LOOP_FINISHED);
+    match finished.as_deref() {
+        Some("true") => None,
+        _ => next.filter(|n| !n.is_empty())
+    }
+}
+
+/// derived from the upstream and the task, so a retried task gets the same loop id.
+fn loop_id(upstream: &Instance, task_id: &str) -> String {
+    let mut hasher = SipHasher24::new();
+    for one in &[upstream.get_key(), task_id.to_string()] {
+        hasher.write(&(one.len() as u64).to_le_bytes());
+        hasher.write(one.as_bytes());
+    }
+    format!("{:x}", hasher.finish())
+}
+
+fn mark_finished(ins: &mut Instance) {
+    ins.sys_mut().set_loop_finished(true);
+}
+
+#[cfg(test)]
+mod test {
+    use futures::executor::block_on;
+    use futures::future::ready;
+
+    use crate::BizObject;
+
+    use super::*;
+
+    fn meta(setting: &str) -> Meta {
+        let mut meta = Meta::new("sale/sum", 1, MetaType::Loop).unwrap();
+        meta.set_setting(setting).unwrap();
+        meta
+    }
+
+    /// pages of [1,2], [3,4], [5]
+    fn page(para: &ConverterParameter) -> (Vec<i32>, Option<String>) {
+        let next: usize = para.from.sys_context.get(CONTEXT_LOOP_NEXT).map(|n| n.parse().unwrap()).unwrap_or(0);
+        let all = [1, 2, 3, 4, 5];
+        let end = (next + 2).min(all.len());
+        let cursor = if end < all.len() { Some(end.to_string()) } else { None };
+        (all[next..end].to_vec(), cursor)
+    }
+
+    fn with_content(content: String) -> Instance {
+        Instance { data: BizObject { content, ..Default::default() }, ..Default::default() }
+    }
+
+    fn with_next(mut ins: Instance, next: Option<String>) -> Instance {
+        if let Some(next) = next {
+            ins.sys_context.insert(CONTEXT_LOOP_NEXT.to_string(), next);
+        }
+        ins
+    }
+
+    #[test]
+    fn check_meta_test() {
+        assert_eq!(check_loop_meta(&meta(r#"{"multi_meta":["B:a:1"],"only_one":true}"#)).is_ok(), true);
+        let rtn = check_loop_meta(&meta(r#"{"multi_meta":["B:a:1","B:b:1"],"only_one":true}"#));
+        assert_eq!(rtn, Err(NatureError::VerifyError("only_one of loop meta [L:sale/sum:1] requires one multi_meta, but get 2".to_string())));
+        assert_eq!(check_loop_meta(&meta("")).is_err(), true);
+        let business = Meta::new("a", 1, MetaType::Business).unwrap();
+        assert_eq!(check_loop_meta(&business), Err(NatureError::VerifyError("[B:a:1] is not a loop meta".to_string())));
+    }
+
+    #[test]
+    fn each_batch_test() {
+        let upstream = Instance::new("sale/order").unwrap();
+        let mut c = LoopCoordinator::new(&meta(r#"{"multi_meta":["B:item:1"]}"#), &upstream, "t1").unwrap();
+        let converter = |para: ConverterParameter| {
+            let (items, next) = page(&para);
+            let instances = items.iter().enumerate().map(|(i, n)| {
+                let ins = with_content(n.to_string());
+                if i == 0 { with_next(ins, next.clone()) } else { ins }
+            }).collect();
+            ready(ConverterReturned::Instances(instances))
+        };
+        let rtn = block_on(c.run(10, converter)).unwrap();
+        let contents: Vec<&str> = rtn.iter().map(|one| one.content.as_str()).collect();
+        assert_eq!(contents, vec!["1", "2", "3", "4", "5"]);
+        assert_eq!(c.is_finished(), true);
+        assert_eq!(c.context().len, 3);
+        assert_eq!(rtn[0].meta, "B:item:1");
+        assert_eq!(rtn[0].from, Some(FromInstance::from(&upstream)));
+        assert_eq!(rtn[0].sys_context[CONTEXT_LOOP_ID], c.loop_id());
+        assert_eq!(rtn[0].sys_context[CONTEXT_LOOP_TASK], "t1");
+        assert_eq!(rtn[0].sys_context.contains_key(CONTEXT_LOOP_NEXT), false);
+        assert_eq!(rtn[0].sys_context.contains_key(CONTEXT_LOOP_FINISHED), false);
+        assert_eq!(rtn[4].sys_context[CONTEXT_LOOP_FINISHED], "true");
+        assert_eq!(c.apply(ConverterReturned::None).is_err(), true);
+    }
+
+    #[test]
+    fn loop_id_test() {
+        let upstream = Instance::new("sale/order").unwrap();
+        let meta = meta(r#"{"multi_meta":["B:item:1"]}"#);
+        let c = LoopCoordinator::new(&meta, &upstream, "t1").unwrap();
+        assert_eq!(c.loop_id(), LoopCoordinator::new(&meta, &upstream, "t1").unwrap().loop_id());
+        assert_ne!(c.loop_id(), LoopCoordinator::new(&meta, &upstream, "t2").unwrap().loop_id());
+        let other = Instance::new("sale/refund").unwrap();
+        assert_ne!(c.loop_id(), LoopCoordinator::new(&meta, &other, "t1").unwrap().loop_id());
+    }
+
+    #[test]
+    fn only_one_test() {
+        let upstream = Instance::new("sale/order").unwrap();
+        let mut c = LoopCoordinator::new(&meta(r#"{"multi_meta":["B:total:1"],"only_one":true}"#), &upstream, "t1").unwrap();
+        let converter = |para: ConverterParameter| {
+            let (items, next) = page(&para);
+            let last: i32 = para.last_state.map(|l| l.content.parse().unwrap()).unwrap_or(0);
+            let ins = with_content((last + items.iter().sum::<i32>()).to_string());
+            ready(ConverterReturned::Instances(vec![with_next(ins, next)]))
+        };
+        let rtn = block_on(c.run(10, converter)).unwrap();
+        assert_eq!(rtn.len(), 1);
+        assert_eq!(rtn[0].content, "15");
+        assert_eq!(rtn[0].meta, "B:total:1");
+        assert_eq!(rtn[0].sys_context[CONTEXT_LOOP_FINISHED], "true");
+    }
+
+    #[test]
+    fn error_test() {
+        let upstream = Instance::new("sale/order").unwrap();
+        let m = meta(r#"{"multi_meta":["B:total:1"],"only_one":true}"#);
+        // not move forward
+        let mut c = LoopCoordinator::new(&m, &upstream, "t1").unwrap();
+        let stuck = |_: ConverterParameter| ready(ConverterReturned::Instances(vec![with_next(Instance::default(), Some("1".to_string()))]));
+        assert_eq!(block_on(c.run(10, stuck)).unwrap_err().to_string().contains("did not move forward"), true);
+        // too many batches
+        let mut c = LoopCoordinator::new(&m, &upstream, "t1").unwrap();
+        let endless = |para: ConverterParameter| {
+            let next = para.from.sys_context.get(CONTEXT_LOOP_NEXT).cloned().unwrap_or_default() + "a";
+            ready(ConverterReturned::Instances(vec![with_next(Instance::default(), Some(next))]))
+        };
+        assert_eq!(block_on(c.run(3, endless)).unwrap_err().to_string().contains("exceeded 3 batches"), true);
+        // more than one for only_one, the rejected batch does not move the cursor
+        let mut c = LoopCoordinator::new(&m, &upstream, "t1").unwrap();
+        let two = vec![with_next(Instance::default(), Some("p2".to_string())), Instance::default()];
+        assert_eq!(c.apply(ConverterReturned::Instances(two)).is_err(), true);
+        assert_eq!(c.context(), &LoopContext::default());
+        assert_eq!(c.is_finished(), false);
+        // undefined meta
+        let mut c = LoopCoordinator::new(&meta(r#"{"multi_meta":["B:a:1","B:b:1"]}"#), &upstream, "t1").unwrap();
+        let undefined = with_next(Instance::new("c").unwrap(), Some("p2".to_string()));
+        assert_eq!(c.apply(ConverterReturned::Instances(vec![undefined])).is_err(), true);
+        assert_eq!(c.context(), &LoopContext::default());
+        // errors from converter
+        let mut c = LoopCoordinator::new(&m, &upstream, "t1").unwrap();
+        assert_eq!(c.apply(ConverterReturned::EnvError("e".to_string())), Err(NatureError::EnvironmentError("e".to_string())));
+        assert_eq!(c.apply(ConverterReturned::Delay(1)).is_err(), true);
+        // finished by flag
+        let mut ins = with_next(Instance::default(), Some("1".to_string()));
+        ins.sys_mut().set_loop_finished(true);
+        assert_eq!(c.apply(ConverterReturned::Instances(vec![ins])).unwrap().len(), 1);
+        assert_eq!(c.is_finished(), true);
+    }
+
+    #[test]
+    fn empty_last_batch_test() {
+        let upstream = Instance::new("sale/order").unwrap();
+        let mut c = LoopCoordinator::new(&meta(r#"{"multi_meta":["B:item:1"]}"#), &upstream, "t1").unwrap();
+        let first = c.apply(ConverterReturned::Instances(vec![with_next(Instance::default(), Some("p2".to_string()))])).unwrap();
+        assert_eq!(c.apply(ConverterReturned::None), Ok(vec![]));
+        assert_eq!(c.is_finished(), true);
+        assert_eq!(first[0].sys_context.contains_key(CONTEXT_LOOP_FINISHED), false);
+        // the accumulated one of only_one is emitted
+        let mut c = LoopCoordinator::new(&meta(r#"{"multi_meta":["B:total:1"],"only_one":true}"#), &upstream, "t1").unwrap();
+        let acc = with_next(with_content("3".to_string()), Some("p2".to_string()));
+        assert_eq!(c.apply(ConverterReturned::Instances(vec![acc])), Ok(vec![]));
+        let rtn = c.apply(ConverterReturned::None).unwrap();
+        assert_eq!(rtn[0].content, "3");
+        assert_eq!(rtn[0].sys_context[CONTEXT_LOOP_FINISHED], "true");
+    }
+}
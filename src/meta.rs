@@ -2,7 +2,9 @@ use std::collections::{HashMap, HashSet};
 use std::collections::btree_map::BTreeMap;
 use std::str::FromStr;
 
-use crate::{CheckType, MetaSetting, SEPARATOR_META, SEPARATOR_META_KEY, State, StatePath};
+use itertools::Itertools;
+
+use crate::{CheckType, MetaSetting, SEPARATOR_META, SEPARATOR_META_KEY, State, StatePath, TargetState};
 use crate::NatureError::VerifyError;
 use crate::state::States;
 
@@ -130,9 +132,17 @@ impl Meta {
         match states {
             Some(ss) => {
                 Self::avoid_same_name(&ss)?;
+                let former = self.clone();
                 self.init_check_list(&ss, 0, &mut Default::default());
                 self.state = Some(ss);
                 self.is_state = true;
+                // the transitions may be set before the states
+                if let Some(setting) = &former.setting {
+                    if let Err(e) = self.check_transition_defined(setting) {
+                        *self = former;
+                        return Err(e);
+                    }
+                }
             }
             _ => {
                 match &self.setting {
@@ -251,6 +261,7 @@ impl Meta {
             if setting.is_state {
                 self.is_state = true;
             }
+            self.check_transition_defined(&setting)?;
             self.setting = Some(setting);
         } else {
             self.setting = None;
@@ -258,6 +269,75 @@ impl Meta {
         Ok(())
     }
 
+    fn check_transition_defined(&self, setting: &MetaSetting) -> Result<()> {
+        if setting.transitions.is_empty() {
+            return Ok(());
+        }
+        if !self.is_state {
+            return Err(VerifyError(format!("[{}] is not a state meta, transitions can't be used", self.meta_string())));
+        }
+        if self.state.is_none() {
+            return Ok(());
+        }
+        let undefined = setting.transitions.iter()
+            .flat_map(|(to, from)| from.iter().chain(std::iter::once(to)))
+            .find(|one| !self.has_state_name(one));
+        match undefined {
+            Some(one) => Err(VerifyError(format!("transition state [{}] does not defined in meta: {}", one, self.meta_string()))),
+            None => Ok(())
+        }
+    }
+
+    /// check whether the `target` states can be reached from the `old` states
+    /// according to the `transitions` defined in `MetaSetting`:
+    /// - a state which is a key of `transitions` can only be added from one of its sources.
+    /// - a state which is only a source in `transitions` is an initial state, it can only be added to empty states.
+    /// - a state named in `transitions` can only be removed when a state it can go to is added.
+    /// - states which are not named in `transitions` are free.
+    pub fn check_transition(&self, old: &HashSet<String>, target: &TargetState) -> Result<()> {
+        if !self.is_state {
+            return Err(VerifyError(format!("[{}] is not a state meta", self.meta_string())));
+        }
+        let transitions = match &self.setting {
+            Some(setting) => &setting.transitions,
+            None => return Ok(())
+        };
+        let is_source = |state: &str| transitions.values().any(|from| from.contains(state));
+        let empty: Vec<String> = vec![];
+        let add = target.add.as_ref().unwrap_or(&empty);
+        for one in add {
+            if old.contains(one) {
+                continue;
+            }
+            if let Some(allowed) = transitions.get(one) {
+                if !allowed.iter().any(|from| old.contains(from)) {
+                    let msg = format!("illegal transition from [{}] to [{}] in meta: {}, it should be from one of [{}]",
+                                      old.iter().sorted().join(","), one, self.meta_string(), allowed.iter().join(","));
+                    warn!("{}", &msg);
+                    return Err(NatureError::VerifyError(msg));
+                }
+            } else if is_source(one) && !old.is_empty() {
+                let msg = format!("illegal transition from [{}] to [{}] in meta: {}, it's an initial state",
+                                  old.iter().sorted().join(","), one, self.meta_string());
+                warn!("{}", &msg);
+                return Err(NatureError::VerifyError(msg));
+            }
+        }
+        for one in target.remove.as_ref().unwrap_or(&empty) {
+            if !old.contains(one) || !(transitions.contains_key(one) || is_source(one)) {
+                continue;
+            }
+            let moved = add.iter().any(|to| matches!(transitions.get(to), Some(from) if from.contains(one)));
+            if !moved {
+                let msg = format!("illegal removing of [{}] in meta: {}, it should be replaced by a state it can go to",
+                                  one, self.meta_string());
+                warn!("{}", &msg);
+                return Err(NatureError::VerifyError(msg));
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_setting(&self) -> Option<MetaSetting> {
         self.setting.clone()
     }
@@ -414,7 +494,11 @@ mod verify_test {
             master: None,
             multi_meta: Default::default(),
             cache_saved: false,
-            only_one: false
+            only_one: false,
+            transitions: Default::default(),
+            content_schema: None,
+            context_schema: None,
+            time_ordered_id: false,
         }.to_json().unwrap();
         let _ = meta.set_setting(&setting);
         let set: Vec<String> = vec!["a".to_string()];
@@ -467,7 +551,7 @@ mod verify_test {
     #[test]
     fn parent_in_mutex() {
         let mut meta = Meta::new("/hello", 1, MetaType::Business).unwrap();
-        let _ = match State::string_to_states("a|b[c|d,e]]") {
+        let _ = match State::string_to_states("a|b[c|d,e]") {
             Ok((ss, _)) => meta.set_states(Some(ss)),
             _ => { panic!("should have some") }
         };
@@ -499,3 +583,106 @@ mod verify_test {
         assert_eq!(rtn.contains(&"e".to_string()), true);
     }
 }
+
+#[cfg(test)]
+mod transition_test {
+    use super::*;
+
+    fn order_meta() -> Meta {
+        let mut meta = Meta::new("/order", 1, MetaType::Business).unwrap();
+        let (ss, _) = State::string_to_states("new|paid|shipped|refunded").unwrap();
+        meta.set_states(Some(ss)).unwrap();
+        meta.set_setting(r#"{"transitions":["new -> paid -> shipped","paid -> refunded"]}"#).unwrap();
+        meta
+    }
+
+    fn target(add: &str) -> TargetState {
+        TargetState {
+            add: Some(vec![add.to_string()]),
+            ..Default::default()
+        }
+    }
+
+    fn states(ss: &[&str]) -> HashSet<String> {
+        ss.iter().map(|one| one.to_string()).collect()
+    }
+
+    #[test]
+    fn legal() {
+        let meta = order_meta();
+        assert_eq!(meta.check_transition(&states(&[]), &target("new")), Ok(()));
+        assert_eq!(meta.check_transition(&states(&["new"]), &target("paid")), Ok(()));
+        assert_eq!(meta.check_transition(&states(&["paid"]), &target("shipped")), Ok(()));
+        assert_eq!(meta.check_transition(&states(&["paid"]), &target("refunded")), Ok(()));
+        assert_eq!(meta.check_transition(&states(&["paid"]), &target("paid")), Ok(()));
+        assert_eq!(meta.check_transition(&states(&["paid"]), &TargetState::default()), Ok(()));
+    }
+
+    #[test]
+    fn illegal() {
+        let meta = order_meta();
+        assert_eq!(meta.check_transition(&states(&["new"]), &target("shipped")),
+                   Err(NatureError::VerifyError("illegal transition from [new] to [shipped] in meta: B:order:1, it should be from one of [paid]".to_string())));
+        assert_eq!(meta.check_transition(&states(&[]), &target("paid")).is_err(), true);
+        assert_eq!(meta.check_transition(&states(&["shipped"]), &target("refunded")).is_err(), true);
+    }
+
+    #[test]
+    fn initial_state() {
+        let meta = order_meta();
+        assert_eq!(meta.check_transition(&states(&["shipped"]), &target("new")),
+                   Err(NatureError::VerifyError("illegal transition from [shipped] to [new] in meta: B:order:1, it's an initial state".to_string())));
+        assert_eq!(meta.check_transition(&states(&["new"]), &target("new")), Ok(()));
+    }
+
+    #[test]
+    fn remove() {
+        let meta = order_meta();
+        let remove = |rm: &str, add: Option<&str>| TargetState {
+            add: add.map(|one| vec![one.to_string()]),
+            remove: Some(vec![rm.to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(meta.check_transition(&states(&["paid"]), &remove("paid", None)),
+                   Err(NatureError::VerifyError("illegal removing of [paid] in meta: B:order:1, it should be replaced by a state it can go to".to_string())));
+        assert_eq!(meta.check_transition(&states(&["paid"]), &remove("paid", Some("shipped"))), Ok(()));
+        assert_eq!(meta.check_transition(&states(&["new"]), &remove("new", Some("paid"))), Ok(()));
+        assert_eq!(meta.check_transition(&states(&["shipped"]), &remove("shipped", Some("refunded"))).is_err(), true);
+        // not in old states or not named in transitions
+        assert_eq!(meta.check_transition(&states(&["new"]), &remove("paid", None)), Ok(()));
+        let mut meta = Meta::new("/order", 1, MetaType::Business).unwrap();
+        let (ss, _) = State::string_to_states("new,paid,flagged").unwrap();
+        meta.set_states(Some(ss)).unwrap();
+        meta.set_setting(r#"{"transitions":["new -> paid"]}"#).unwrap();
+        assert_eq!(meta.check_transition(&states(&["new", "flagged"]), &remove("flagged", None)), Ok(()));
+    }
+
+    #[test]
+    fn undefined_state() {
+        let mut meta = Meta::new("/order", 1, MetaType::Business).unwrap();
+        let (ss, _) = State::string_to_states("new|paid").unwrap();
+        meta.set_states(Some(ss)).unwrap();
+        let rtn = meta.set_setting(r#"{"transitions":["new -> payed"]}"#);
+        assert_eq!(rtn, Err(NatureError::VerifyError("transition state [payed] does not defined in meta: B:order:1".to_string())));
+    }
+
+    #[test]
+    fn undefined_state_set_later() {
+        let mut meta = Meta::new("/order", 1, MetaType::Business).unwrap();
+        meta.set_setting(r#"{"is_state":true,"transitions":["new -> payed"]}"#).unwrap();
+        let (ss, _) = State::string_to_states("new|paid").unwrap();
+        let rtn = meta.set_states(Some(ss));
+        assert_eq!(rtn, Err(NatureError::VerifyError("transition state [payed] does not defined in meta: B:order:1".to_string())));
+        assert_eq!(meta.get_states(), None);
+        let (ss, _) = State::string_to_states("new|payed").unwrap();
+        assert_eq!(meta.set_states(Some(ss)), Ok(()));
+    }
+
+    #[test]
+    fn not_state_meta() {
+        let mut meta = Meta::new("/order", 1, MetaType::Business).unwrap();
+        let rtn = meta.set_setting(r#"{"transitions":["new -> paid"]}"#);
+        assert_eq!(rtn.is_err(), true);
+        assert_eq!(meta.check_transition(&states(&[]), &target("new")).is_err(), true);
+    }
+}
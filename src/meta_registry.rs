@@ -0,0 +1,207 @@
+use std::collections::btree_map::BTreeMap;
+use std::collections::btree_set::BTreeSet;
+
+use itertools::Itertools;
+
+use crate::{Meta, MetaResolver, NatureError, Result, SEPARATOR_META_KEY};
+
+/// A node of the key tree, each level is a part of the key separated by `SEPARATOR_META_KEY`
+#[derive(Debug, Clone, Default)]
+struct KeyNode {
+    children: BTreeMap<String, KeyNode>,
+    /// meta strings whose key ends at this node
+    metas: BTreeSet<String>,
+}
+
+impl KeyNode {
+    fn find(&self, parts: &[&str]) -> Option<&KeyNode> {
+        match parts.split_first() {
+            None => Some(self),
+            Some((first, remained)) => self.children.get(*first)?.find(remained)
+        }
+    }
+
+    fn insert(&mut self, parts: &[&str], meta: &str) {
+        match parts.split_first() {
+            None => { self.metas.insert(meta.to_string()); }
+            Some((first, remained)) => self.children.entry(first.to_string()).or_default().insert(remained, meta)
+        }
+    }
+
+    /// return true if this node is empty after removed
+    fn remove(&mut self, parts: &[&str], meta: &str) -> bool {
+        match parts.split_first() {
+            None => { self.metas.remove(meta); }
+            Some((first, remained)) => {
+                let empty = match self.children.get_mut(*first) {
+                    Some(child) => child.remove(remained, meta),
+                    None => false
+                };
+                if empty {
+                    self.children.remove(*first);
+                }
+            }
+        }
+        self.metas.is_empty() && self.children.is_empty()
+    }
+
+    fn all_metas(&self, rtn: &mut Vec<String>) {
+        rtn.extend(self.metas.iter().cloned());
+        self.children.values().for_each(|child| child.all_metas(rtn));
+    }
+}
+
+/// Hold `Meta`s in memory, can be found by meta string or by the hierarchical key.
+#[derive(Debug, Clone, Default)]
+pub struct MetaRegistry {
+    resolver: MetaResolver,
+    tree: KeyNode,
+}
+
+fn key_parts(key: &str) -> Vec<&str> {
+    key.split(&*SEPARATOR_META_KEY).filter(|one| !one.is_empty()).collect()
+}
+
+impl MetaRegistry {
+    /// the meta with the same meta string will be replaced
+    pub fn register(&mut self, meta: Meta) {
+        let key = meta.get_key();
+        self.tree.insert(&key_parts(&key), &meta.meta_string());
+        self.resolver.add(meta);
+    }
+
+    pub fn unregister(&mut self, meta_str: &str) -> Option<Meta> {
+        let rtn = self.resolver.remove(meta_str)?;
+        self.tree.remove(&key_parts(&rtn.get_key()), &rtn.meta_string());
+        Some(rtn)
+    }
+
+    /// `meta_str`'s format : [MetaType]:[key]:[version|*|latest]
+    pub fn get(&self, meta_str: &str) -> Result<&Meta> {
+        self.resolver.resolve(meta_str)
+    }
+
+    /// all versions of a meta in ascending order, `type_and_key`'s format : [MetaType]:[key]
+    pub fn versions(&self, type_and_key: &str) -> Vec<&Meta> {
+        self.resolver.versions(type_and_key)
+    }
+
+    /// the keys of the next level under `prefix`, e.g. `sale/` may return `sale/item` and `sale/order`
+    pub fn children(&self, prefix: &str) -> Vec<String> {
+        let parts = key_parts(prefix);
+        match self.tree.find(&parts) {
+            None => vec![],
+            Some(node) => node.children.keys().map(|one| {
+                let mut key = parts.clone();
+                key.push(one);
+                key.join(&*SEPARATOR_META_KEY)
+            }).collect()
+        }
+    }
+
+    /// all metas whose key is `prefix` or under `prefix`
+    pub fn descendants(&self, prefix: &str) -> Vec<&Meta> {
+        let mut metas: Vec<String> = vec![];
+        if let Some(node) = self.tree.find(&key_parts(prefix)) {
+            node.all_metas(&mut metas);
+        }
+        metas.iter().filter_map(|one| self.resolver.resolve(one).ok()).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&Meta> {
+        self.resolver.iter()
+    }
+
+    /// check each `master` and `multi_meta` of the `MetaSetting` is registered
+    pub fn verify(&self) -> Result<()> {
+        let mut errors: Vec<String> = vec![];
+        for meta in self.iter() {
+            let setting = match meta.get_setting() {
+                Some(setting) => setting,
+                None => continue
+            };
+            if let Some(master) = &setting.master {
+                if self.get(master).is_err() {
+                    errors.push(format!("{} : master [{}] unregistered", meta.meta_string(), master));
+                }
+            }
+            setting.multi_meta.iter().filter(|one| self.get(one).is_err()).for_each(|one| {
+                errors.push(format!("{} : multi_meta [{}] unregistered", meta.meta_string(), one));
+            });
+        }
+        if errors.is_empty() {
+            return Ok(());
+        }
+        let msg = errors.iter().join("; ");
+        warn!("{}", &msg);
+        Err(NatureError::VerifyError(msg))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::MetaType;
+
+    use super::*;
+
+    fn registry() -> MetaRegistry {
+        let mut rtn = MetaRegistry::default();
+        rtn.register(Meta::new("sale/order", 1, MetaType::Business).unwrap());
+        rtn.register(Meta::new("sale/order", 2, MetaType::Business).unwrap());
+        rtn.register(Meta::new("sale/order/item", 1, MetaType::Business).unwrap());
+        rtn.register(Meta::new("sale/return", 1, MetaType::Business).unwrap());
+        rtn.register(Meta::new("finance/bill", 1, MetaType::Business).unwrap());
+        rtn
+    }
+
+    #[test]
+    fn children_test() {
+        let r = registry();
+        assert_eq!(r.children(""), vec!["finance", "sale"]);
+        assert_eq!(r.children("sale/"), vec!["sale/order", "sale/return"]);
+        assert_eq!(r.children("/sale/order"), vec!["sale/order/item"]);
+        assert_eq!(r.children("sale/order/item").is_empty(), true);
+        assert_eq!(r.children("none").is_empty(), true);
+    }
+
+    #[test]
+    fn descendants_test() {
+        let r = registry();
+        let rtn: Vec<String> = r.descendants("sale/order").iter().map(|m| m.meta_string()).collect();
+        assert_eq!(rtn, vec!["B:sale/order:1", "B:sale/order:2", "B:sale/order/item:1"]);
+        assert_eq!(r.descendants("").len(), 5);
+    }
+
+    #[test]
+    fn get_and_versions_test() {
+        let r = registry();
+        assert_eq!(r.get("B:sale/order:1").unwrap().version, 1);
+        assert_eq!(r.get("B:sale/order:*").unwrap().version, 2);
+        assert_eq!(r.get("B:sale/none:1").is_err(), true);
+        assert_eq!(r.versions("B:sale/order").len(), 2);
+    }
+
+    #[test]
+    fn unregister_test() {
+        let mut r = registry();
+        assert_eq!(r.unregister("B:sale/order/item:1").is_some(), true);
+        assert_eq!(r.children("sale/order").is_empty(), true);
+        assert_eq!(r.unregister("B:sale/order/item:1").is_none(), true);
+        r.unregister("B:finance/bill:1");
+        assert_eq!(r.children(""), vec!["sale"]);
+    }
+
+    #[test]
+    fn verify_test() {
+        let mut r = registry();
+        assert_eq!(r.verify(), Ok(()));
+        let mut meta = Meta::new("sale/settle", 1, MetaType::Business).unwrap();
+        meta.set_setting(r#"{"is_state":true,"master":"B:sale/order:*","multi_meta":["B:sale/return:1"]}"#).unwrap();
+        r.register(meta);
+        assert_eq!(r.verify(), Ok(()));
+        let mut meta = Meta::new("sale/bad", 1, MetaType::Business).unwrap();
+        meta.set_setting(r#"{"is_state":true,"master":"B:sale/none:1","multi_meta":["B:sale/return:1","B:sale/return:2"]}"#).unwrap();
+        r.register(meta);
+        assert_eq!(r.verify(), Err(NatureError::VerifyError("B:sale/bad:1 : master [B:sale/none:1] unregistered; B:sale/bad:1 : multi_meta [B:sale/return:2] unregistered".to_string())));
+    }
+}
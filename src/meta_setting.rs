@@ -1,7 +1,8 @@
+use std::collections::btree_map::BTreeMap;
 use std::collections::btree_set::BTreeSet;
 use std::str::FromStr;
 
-use crate::{FromInstance, Instance, is_default, NatureError, Result};
+use crate::{FromInstance, Instance, is_default, NatureError, Result, SchemaSetting};
 
 #[derive(Debug, Clone, Default, PartialEq, Ord, PartialOrd, Eq)]
 #[derive(Serialize, Deserialize)]
@@ -21,6 +22,17 @@ pub struct MetaSetting {
     /// only used by `MetaType::Loop`, has only one instance generated when loop finished.
     /// Requirement: multi_meta should has only one item
     pub only_one: bool,
+    /// Only useful for state-meta.
+    /// key: the state to go, value: the states which can go to the key.
+    /// A state which is not a key can be reached from any state.
+    pub transitions: BTreeMap<String, BTreeSet<String>>,
+    /// JSON Schema for `Instance.content`
+    pub content_schema: Option<SchemaSetting>,
+    /// JSON Schema for `Instance.context`
+    pub context_schema: Option<SchemaSetting>,
+    /// Use time-ordered id instead of content hash for the instance without para,
+    /// so that the same events happened at different time will not be treated as one.
+    pub time_ordered_id: bool,
 }
 
 impl From<MetaSettingTemp> for MetaSetting {
@@ -35,6 +47,18 @@ impl From<MetaSettingTemp> for MetaSetting {
             },
             cache_saved: input.cache_saved,
             only_one: input.only_one,
+            transitions: {
+                let mut rtn = BTreeMap::<String, BTreeSet<String>>::new();
+                input.transitions.iter().for_each(|one| {
+                    transition_pairs(one).unwrap_or_default().into_iter().for_each(|(from, to)| {
+                        rtn.entry(to).or_default().insert(from);
+                    });
+                });
+                rtn
+            },
+            content_schema: input.content_schema,
+            context_schema: input.context_schema,
+            time_ordered_id: input.time_ordered_id,
         }
     }
 }
@@ -44,10 +68,29 @@ impl FromStr for MetaSetting {
 
     fn from_str(s: &str) -> Result<Self> {
         let tmp: MetaSettingTemp = serde_json::from_str(s)?;
+        for one in &tmp.transitions {
+            transition_pairs(one)?;
+        }
+        if let Some(schema) = &tmp.content_schema {
+            schema.verify()?;
+        }
+        if let Some(schema) = &tmp.context_schema {
+            schema.verify()?;
+        }
         Ok(tmp.into())
     }
 }
 
+/// `line`'s format : state1 -> state2 -> state3 ...
+fn transition_pairs(line: &str) -> Result<Vec<(String, String)>> {
+    let states: Vec<&str> = line.split("->").map(|one| one.trim()).collect();
+    if states.len() < 2 || states.iter().any(|one| one.is_empty()) {
+        let msg = format!("transition format should be [state]->[state]..., but get: {}", line);
+        return Err(NatureError::VerifyError(msg));
+    }
+    Ok(states.windows(2).map(|pair| (pair[0].to_string(), pair[1].to_string())).collect())
+}
+
 impl From<MetaSetting> for MetaSettingTemp {
     fn from(input: MetaSetting) -> Self {
         MetaSettingTemp {
@@ -60,6 +103,16 @@ impl From<MetaSetting> for MetaSettingTemp {
             },
             cache_saved: input.cache_saved,
             only_one: input.only_one,
+            transitions: {
+                let mut rtn: Vec<String> = vec![];
+                input.transitions.into_iter().for_each(|(to, from)| {
+                    from.into_iter().for_each(|one| rtn.push(format!("{} -> {}", one, to)));
+                });
+                rtn
+            },
+            content_schema: input.content_schema,
+            context_schema: input.context_schema,
+            time_ordered_id: input.time_ordered_id,
         }
     }
 }
@@ -115,6 +168,20 @@ struct MetaSettingTemp {
     #[serde(skip_serializing_if = "is_default")]
     #[serde(default)]
     pub only_one: bool,
+    /// each of the item's format is state1 -> state2 -> state3 ...
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub transitions: Vec<String>,
+    /// an object for inline schema, or a string for referenced schema
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub content_schema: Option<SchemaSetting>,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub context_schema: Option<SchemaSetting>,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub time_ordered_id: bool,
 }
 
 #[cfg(test)]
@@ -141,6 +208,10 @@ mod test {
             multi_meta: set,
             cache_saved: false,
             only_one: false,
+            transitions: Default::default(),
+            content_schema: None,
+            context_schema: None,
+            time_ordered_id: false,
         };
         let a = Instance::new("a").unwrap();
         let b = Instance::new("b").unwrap();
@@ -167,6 +238,10 @@ mod test {
             multi_meta: set,
             cache_saved: false,
             only_one: false,
+            transitions: Default::default(),
+            content_schema: None,
+            context_schema: None,
+            time_ordered_id: false,
         };
         let a = Instance::default();
         let b = Instance::default();
@@ -185,4 +260,25 @@ mod test {
         let result = MetaSetting::from(result);
         assert_eq!(result.cache_saved, true);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn transitions_test() {
+        let setting = r#"{"transitions":["new -> paid -> shipped","paid->refunded"]}"#;
+        let result = MetaSetting::from_str(setting).unwrap();
+        assert_eq!(result.transitions.len(), 3);
+        assert_eq!(result.transitions["paid"].iter().collect::<Vec<_>>(), vec!["new"]);
+        assert_eq!(result.transitions["shipped"].iter().collect::<Vec<_>>(), vec!["paid"]);
+        assert_eq!(result.transitions["refunded"].iter().collect::<Vec<_>>(), vec!["paid"]);
+        let json = result.to_json().unwrap();
+        assert_eq!(json, r#"{"transitions":["new -> paid","paid -> refunded","paid -> shipped"]}"#);
+        assert_eq!(MetaSetting::from_str(&json).unwrap(), result);
+    }
+
+    #[test]
+    fn transitions_error_test() {
+        let result = MetaSetting::from_str(r#"{"transitions":["new"]}"#);
+        assert_eq!(result, Err(NatureError::VerifyError("transition format should be [state]->[state]..., but get: new".to_string())));
+        let result = MetaSetting::from_str(r#"{"transitions":["new -> -> paid"]}"#);
+        assert_eq!(result.is_err(), true);
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::btree_map::BTreeMap;
+use std::collections::btree_set::BTreeSet;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use itertools::Itertools;
+
+use crate::{Meta, MetaType, NatureError, Result, SEPARATOR_META, State};
+
+/// The version part of a meta reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaVersion {
+    Exact(u32),
+    /// written as `*` or `latest`
+    Latest,
+}
+
+impl FromStr for MetaVersion {
+    type Err = NatureError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "*" | "latest" => Ok(MetaVersion::Latest),
+            _ => match s.parse::<u32>() {
+                Ok(ver) => Ok(MetaVersion::Exact(ver)),
+                Err(_) => Err(NatureError::VerifyError(format!("version should be a number, `*` or `latest`, but get: {}", s)))
+            }
+        }
+    }
+}
+
+/// A reference to `Meta`, format : [MetaType]:[key]:[version|*|latest]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaReference {
+    pub meta_type: MetaType,
+    pub key: String,
+    pub version: MetaVersion,
+}
+
+impl FromStr for MetaReference {
+    type Err = NatureError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let x: Vec<&str> = s.split(&*SEPARATOR_META).collect();
+        if x.len() != 3 {
+            return Err(NatureError::VerifyError("format should be [MetaType]:[key]:[version|*|latest]".to_string()));
+        }
+        let meta_type = MetaType::from_prefix(x[0])?;
+        let key = match meta_type {
+            MetaType::Null => "".to_string(),
+            _ => Meta::key_standardize(x[1])?
+        };
+        Ok(MetaReference {
+            meta_type,
+            key,
+            version: MetaVersion::from_str(x[2])?,
+        })
+    }
+}
+
+impl MetaReference {
+    /// [MetaType]:[key], used to find all versions of a meta
+    pub fn type_and_key(&self) -> String {
+        type_and_key(&self.meta_type, &self.key)
+    }
+}
+
+fn type_and_key(meta_type: &MetaType, key: &str) -> String {
+    meta_type.get_prefix() + &*SEPARATOR_META + key
+}
+
+/// Hold all versions of the registered `Meta`s, and resolve the reference to a particular one.
+#[derive(Debug, Clone, Default)]
+pub struct MetaResolver {
+    /// key : [MetaType]:[key]
+    metas: BTreeMap<String, BTreeMap<u32, Meta>>,
+}
+
+impl MetaResolver {
+    /// the meta with the same meta string will be replaced
+    pub fn add(&mut self, meta: Meta) {
+        let key = type_and_key(&meta.get_meta_type(), &meta.get_key());
+        self.metas.entry(key).or_default().insert(meta.version, meta);
+    }
+
+    pub fn remove(&mut self, meta_str: &str) -> Option<Meta> {
+        let reference = MetaReference::from_str(meta_str).ok()?;
+        let key = reference.type_and_key();
+        let versions = self.metas.get_mut(&key)?;
+        let rtn = match reference.version {
+            MetaVersion::Exact(ver) => versions.remove(&ver),
+            MetaVersion::Latest => {
+                let last = *versions.keys().next_back()?;
+                versions.remove(&last)
+            }
+        };
+        if versions.is_empty() {
+            self.metas.remove(&key);
+        }
+        rtn
+    }
+
+    /// `type_and_key`'s format : [MetaType]:[key]
+    pub fn latest(&self, type_and_key: &str) -> Option<&Meta> {
+        self.metas.get(type_and_key)?.values().next_back()
+    }
+
+    /// all versions of a meta in ascending order, `type_and_key`'s format : [MetaType]:[key]
+    pub fn versions(&self, type_and_key: &str) -> Vec<&Meta> {
+        match self.metas.get(type_and_key) {
+            Some(versions) => versions.values().collect(),
+            None => vec![]
+        }
+    }
+
+    /// `meta_str`'s format : [MetaType]:[key]:[version|*|latest]
+    pub fn resolve(&self, meta_str: &str) -> Result<&Meta> {
+        let reference = MetaReference::from_str(meta_str)?;
+        let versions = self.metas.get(&reference.type_and_key());
+        let rtn = match (versions, reference.version) {
+            (None, _) => None,
+            (Some(versions), MetaVersion::Exact(ver)) => versions.get(&ver),
+            (Some(versions), MetaVersion::Latest) => versions.values().next_back(),
+        };
+        match rtn {
+            Some(meta) => Ok(meta),
+            None => Err(NatureError::VerifyError(format!("unregistered meta: {}", meta_str)))
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&Meta> {
+        self.metas.values().flat_map(|versions| versions.values())
+    }
+}
+
+/// Changes which will break the `Instance`s or converters made for the older version
+#[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd)]
+pub enum BreakingChange {
+    StateMetaChanged { old: bool, new: bool },
+    StateRemoved(String),
+    MasterChanged { old: Option<String>, new: Option<String> },
+    MultiMetaRemoved(String),
+    TransitionRemoved { from: String, to: String },
+    /// an existing state which was free is constrained by `transitions` now
+    StateConstrained(String),
+    /// existing states become mutex with each other, the `state` is the smaller one of each pair
+    MutexAdded { state: String, with: Vec<String> },
+}
+
+impl Display for BreakingChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakingChange::StateMetaChanged { old, new } => write!(f, "is_state changed from {} to {}", old, new),
+            BreakingChange::StateRemoved(name) => write!(f, "state [{}] removed", name),
+            BreakingChange::MasterChanged { old, new } => write!(f, "master changed from {:?} to {:?}", old, new),
+            BreakingChange::MultiMetaRemoved(meta) => write!(f, "multi_meta [{}] removed", meta),
+            BreakingChange::TransitionRemoved { from, to } => write!(f, "transition [{} -> {}] removed", from, to),
+            BreakingChange::StateConstrained(name) => write!(f, "state [{}] is constrained by transitions", name),
+            BreakingChange::MutexAdded { state, with } => write!(f, "state [{}] became mutex with [{}]", state, with.join(",")),
+        }
+    }
+}
+
+fn state_names(states: &[State], names: &mut BTreeSet<String>) {
+    states.iter().for_each(|one| match one {
+        State::Normal(name) => { names.insert(name.to_string()); }
+        State::Parent(name, children) => {
+            names.insert(name.to_string());
+            state_names(children, names);
+        }
+        State::Mutex(children) => state_names(children, names),
+    })
+}
+
+/// the states which are mutex with each state
+fn mutex_peers(states: &[State], peers: &mut BTreeMap<String, BTreeSet<String>>) {
+    states.iter().for_each(|one| match one {
+        State::Normal(_) => (),
+        State::Parent(_, children) => mutex_peers(children, peers),
+        State::Mutex(children) => {
+            let groups: Vec<BTreeSet<String>> = children.iter().map(|child| {
+                let mut names = BTreeSet::new();
+                state_names(std::slice::from_ref(child), &mut names);
+                names
+            }).collect();
+            for (i, group) in groups.iter().enumerate() {
+                let others: Vec<&String> = groups.iter().enumerate().filter(|(j, _)| *j != i).flat_map(|(_, g)| g.iter()).collect();
+                group.iter().for_each(|name| peers.entry(name.to_string()).or_default().extend(others.iter().map(|o| o.to_string())));
+            }
+            mutex_peers(children, peers);
+        }
+    })
+}
+
+impl Meta {
+    /// compare with the `newer` version, return changes which are not backward compatible.
+    pub fn breaking_changes(&self, newer: &Meta) -> Vec<BreakingChange> {
+        let mut rtn: Vec<BreakingChange> = vec![];
+        if self.is_state() != newer.is_state() {
+            rtn.push(BreakingChange::StateMetaChanged { old: self.is_state(), new: newer.is_state() });
+        }
+        // states
+        let mut old_names = BTreeSet::new();
+        state_names(&self.get_states().unwrap_or_default(), &mut old_names);
+        let mut new_names = BTreeSet::new();
+        state_names(&newer.get_states().unwrap_or_default(), &mut new_names);
+        old_names.difference(&new_names).for_each(|one| rtn.push(BreakingChange::StateRemoved(one.to_string())));
+        let mut old_peers = BTreeMap::new();
+        mutex_peers(&self.get_states().unwrap_or_default(), &mut old_peers);
+        let mut new_peers = BTreeMap::new();
+        mutex_peers(&newer.get_states().unwrap_or_default(), &mut new_peers);
+        let empty = BTreeSet::new();
+        for (state, peers) in &new_peers {
+            if !old_names.contains(state) {
+                continue;
+            }
+            let former = old_peers.get(state).unwrap_or(&empty);
+            let with: Vec<String> = peers.iter()
+                .filter(|one| *one > state && old_names.contains(*one) && !former.contains(*one))
+                .cloned().collect();
+            if !with.is_empty() {
+                rtn.push(BreakingChange::MutexAdded { state: state.to_string(), with });
+            }
+        }
+        // setting
+        let old = self.get_setting().unwrap_or_default();
+        let new = newer.get_setting().unwrap_or_default();
+        if old.master != new.master {
+            rtn.push(BreakingChange::MasterChanged { old: old.master.clone(), new: new.master.clone() });
+        }
+        old.multi_meta.difference(&new.multi_meta).for_each(|one| rtn.push(BreakingChange::MultiMetaRemoved(one.to_string())));
+        for (to, from) in &old.transitions {
+            // no limitation in new version
+            let new_from = match new.transitions.get(to) {
+                Some(new_from) => new_from,
+                None => continue
+            };
+            from.difference(new_from).for_each(|one| rtn.push(BreakingChange::TransitionRemoved { from: one.to_string(), to: to.to_string() }));
+        }
+        // a new key limits the sources, and a state only named as a source can only be the initial state
+        let is_source = |transitions: &BTreeMap<String, BTreeSet<String>>, state: &str| transitions.values().any(|from| from.contains(state));
+        for one in &old_names {
+            if old.transitions.contains_key(one) {
+                continue;
+            }
+            let key_added = new.transitions.contains_key(one);
+            let initial_added = is_source(&new.transitions, one) && !is_source(&old.transitions, one);
+            if key_added || initial_added {
+                rtn.push(BreakingChange::StateConstrained(one.to_string()));
+            }
+        }
+        rtn
+    }
+
+    /// return `VerifyError` if the `newer` version is not backward compatible.
+    pub fn check_compatible(&self, newer: &Meta) -> Result<()> {
+        let changes = self.breaking_changes(newer);
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let msg = format!("[{}] is incompatible with [{}]: {}", newer.meta_string(), self.meta_string(), changes.iter().join("; "));
+        Err(NatureError::VerifyError(msg))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn meta(key: &str, version: u32, states: &str, setting: &str) -> Meta {
+        let mut rtn = Meta::new(key, version, MetaType::Business).unwrap();
+        if !states.is_empty() {
+            let (ss, _) = State::string_to_states(states).unwrap();
+            rtn.set_states(Some(ss)).unwrap();
+        }
+        rtn.set_setting(setting).unwrap();
+        rtn
+    }
+
+    #[test]
+    fn reference_test() {
+        let r = MetaReference::from_str("B:sale/order:*").unwrap();
+        assert_eq!(r.version, MetaVersion::Latest);
+        assert_eq!(r.type_and_key(), "B:sale/order");
+        let r = MetaReference::from_str("B:/sale/order/:latest").unwrap();
+        assert_eq!(r.version, MetaVersion::Latest);
+        assert_eq!(r.key, "sale/order");
+        let r = MetaReference::from_str("B:sale/order:3").unwrap();
+        assert_eq!(r.version, MetaVersion::Exact(3));
+        assert_eq!(MetaReference::from_str("B:sale/order:last").is_err(), true);
+        assert_eq!(MetaReference::from_str("B:sale/order").is_err(), true);
+    }
+
+    #[test]
+    fn resolve_test() {
+        let mut resolver = MetaResolver::default();
+        resolver.add(meta("sale/order", 2, "", ""));
+        resolver.add(meta("sale/order", 10, "", ""));
+        resolver.add(meta("sale/order", 1, "", ""));
+        resolver.add(meta("sale/item", 5, "", ""));
+        assert_eq!(resolver.latest("B:sale/order").unwrap().version, 10);
+        assert_eq!(resolver.latest("B:sale/none").is_none(), true);
+        assert_eq!(resolver.resolve("B:sale/order:*").unwrap().version, 10);
+        assert_eq!(resolver.resolve("B:sale/order:latest").unwrap().version, 10);
+        assert_eq!(resolver.resolve("B:sale/order:2").unwrap().version, 2);
+        assert_eq!(resolver.resolve("B:sale/order:3"), Err(NatureError::VerifyError("unregistered meta: B:sale/order:3".to_string())));
+        assert_eq!(resolver.versions("B:sale/order").iter().map(|m| m.version).collect::<Vec<u32>>(), vec![1, 2, 10]);
+        assert_eq!(resolver.iter().count(), 4);
+        assert_eq!(resolver.remove("B:sale/order:latest").unwrap().version, 10);
+        assert_eq!(resolver.resolve("B:sale/order:*").unwrap().version, 2);
+        assert_eq!(resolver.remove("B:sale/item:5").unwrap().version, 5);
+        assert_eq!(resolver.versions("B:sale/item").is_empty(), true);
+    }
+
+    #[test]
+    fn compatible_test() {
+        let old = meta("order", 1, "new,paid|canceled", r#"{"multi_meta":["B:a:1"]}"#);
+        let new = meta("order", 2, "new,paid|canceled,shipped", r#"{"multi_meta":["B:a:1","B:b:1"]}"#);
+        assert_eq!(old.check_compatible(&new), Ok(()));
+    }
+
+    #[test]
+    fn breaking_test() {
+        let old = meta("order", 1, "new,p[paid|canceled]", r#"{"master":"B:m:1","multi_meta":["B:a:1","B:b:1"],"transitions":["new -> paid"]}"#);
+        let new = meta("order", 2, "new,paid,shipped", r#"{"multi_meta":["B:a:1"],"transitions":["shipped -> paid"]}"#);
+        let changes = old.breaking_changes(&new);
+        assert_eq!(changes, vec![
+            BreakingChange::StateRemoved("canceled".to_string()),
+            BreakingChange::StateRemoved("p".to_string()),
+            BreakingChange::MasterChanged { old: Some("B:m:1".to_string()), new: None },
+            BreakingChange::MultiMetaRemoved("B:b:1".to_string()),
+            BreakingChange::TransitionRemoved { from: "new".to_string(), to: "paid".to_string() },
+        ]);
+        let old = meta("order", 1, "new,paid,shipped", r#"{"transitions":["new -> paid"]}"#);
+        let new = meta("order", 2, "new,paid,shipped", r#"{"transitions":["new -> paid -> shipped"]}"#);
+        assert_eq!(old.breaking_changes(&new), vec![BreakingChange::StateConstrained("shipped".to_string())]);
+        let new = meta("order", 2, "new,paid,shipped", r#"{"transitions":["new -> paid","shipped -> paid"]}"#);
+        assert_eq!(old.breaking_changes(&new), vec![BreakingChange::StateConstrained("shipped".to_string())]);
+        let rtn = meta("order", 1, "new", "").check_compatible(&meta("order", 2, "", ""));
+        assert_eq!(rtn, Err(NatureError::VerifyError("[B:order:2] is incompatible with [B:order:1]: is_state changed from true to false; state [new] removed".to_string())));
+    }
+
+    #[test]
+    fn mutex_test() {
+        let old = meta("order", 1, "new,paid|canceled,shipped", "");
+        // a new state in the mutex group is not breaking
+        let new = meta("order", 2, "new,paid|canceled|closed,shipped", "");
+        assert_eq!(old.breaking_changes(&new), vec![]);
+        let new = meta("order", 2, "new,paid|canceled|shipped", "");
+        assert_eq!(old.breaking_changes(&new), vec![
+            BreakingChange::MutexAdded { state: "canceled".to_string(), with: vec!["shipped".to_string()] },
+            BreakingChange::MutexAdded { state: "paid".to_string(), with: vec!["shipped".to_string()] },
+        ]);
+        let new = meta("order", 2, "p[new,paid]|canceled,shipped", "");
+        assert_eq!(old.check_compatible(&new), Err(NatureError::VerifyError("[B:order:2] is incompatible with [B:order:1]: state [canceled] became mutex with [new]".to_string())));
+    }
+}
@@ -0,0 +1,185 @@
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::{append_para, Instance, NatureError, Result, SEPARATOR_INS_KEY, SEPARATOR_INS_PARA};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    /// context key
+    Context(String),
+    /// json pointer of content
+    Content(String),
+}
+
+/// A template to make para from an `Instance`, e.g. `${context.shop}/${content.date}`.
+/// - `${context.KEY}` : the value of the context `KEY`
+/// - `${content.a.b}` or `${content/a/b}` : the value in content found by json pointer `/a/b`
+///
+/// Other text is kept as it is, but it can't contain `SEPARATOR_INS_KEY`. The rendered values can't contain `SEPARATOR_INS_PARA` or `SEPARATOR_INS_KEY`,
+/// so that the para parts are just the ones written in the template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParaTemplate {
+    parts: Vec<Part>,
+}
+
+impl FromStr for ParaTemplate {
+    type Err = NatureError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts: Vec<Part> = vec![];
+        let mut remained = s;
+        while let Some(begin) = remained.find("${") {
+            if begin > 0 {
+                parts.push(text(&remained[..begin], s)?);
+            }
+            let end = match remained[begin..].find('}') {
+                Some(end) => begin + end,
+                None => return Err(NatureError::VerifyError(format!("`}}` missed in para template: {}", s)))
+            };
+            parts.push(placeholder(&remained[begin + 2..end], s)?);
+            remained = &remained[end + 1..];
+        }
+        if !remained.is_empty() {
+            parts.push(text(remained, s)?);
+        }
+        Ok(ParaTemplate { parts })
+    }
+}
+
+fn text(text: &str, template: &str) -> Result<Part> {
+    match text.contains(&*SEPARATOR_INS_KEY) {
+        true => Err(NatureError::VerifyError(format!("para template can't contain [{}]: {}", *SEPARATOR_INS_KEY, template))),
+        false => Ok(Part::Text(text.to_string()))
+    }
+}
+
+fn placeholder(name: &str, template: &str) -> Result<Part> {
+    if let Some(key) = name.strip_prefix("context.") {
+        if !key.is_empty() {
+            return Ok(Part::Context(key.to_string()));
+        }
+    }
+    if let Some(path) = name.strip_prefix("content.") {
+        if !path.is_empty() {
+            return Ok(Part::Content(format!("/{}", path.replace('.', "/"))));
+        }
+    }
+    if name.starts_with("content/") {
+        return Ok(Part::Content(name["content".len()..].to_string()));
+    }
+    Err(NatureError::VerifyError(format!("unknown placeholder `${{{}}}` in para template: {}", name, template)))
+}
+
+impl ParaTemplate {
+    pub fn render(&self, ins: &Instance) -> Result<String> {
+        let mut content: Option<Value> = None;
+        let mut rtn = String::new();
+        for part in &self.parts {
+            let (name, value) = match part {
+                Part::Text(text) => {
+                    rtn.push_str(text);
+                    continue;
+                }
+                Part::Context(key) => match ins.context.get(key) {
+                    Some(v) => (format!("context.{}", key), v.to_string()),
+                    None => return Err(NatureError::VerifyError(format!("context [{}] not found for para", key)))
+                },
+                Part::Content(path) => {
+                    if content.is_none() {
+                        content = Some(ins.get_content::<Value>()?);
+                    }
+                    let value = match content.as_ref().and_then(|c| c.pointer(path)) {
+                        Some(Value::String(s)) => s.to_string(),
+                        Some(Value::Number(n)) => n.to_string(),
+                        Some(Value::Bool(b)) => b.to_string(),
+                        Some(other) => return Err(NatureError::VerifyError(format!("content [{}] can't be used as para: {}", path, other))),
+                        None => return Err(NatureError::VerifyError(format!("content [{}] not found for para", path)))
+                    };
+                    (format!("content{}", path), value)
+                }
+            };
+            if value.contains(&*SEPARATOR_INS_PARA) || value.contains(&*SEPARATOR_INS_KEY) {
+                let msg = format!("value [{}] of [{}] can't contain [{}] or [{}]", value, name, *SEPARATOR_INS_PARA, *SEPARATOR_INS_KEY);
+                return Err(NatureError::VerifyError(msg));
+            }
+            rtn.push_str(&value);
+        }
+        Ok(rtn)
+    }
+
+    /// render and append to `para` by `append_para`
+    pub fn append_to(&self, para: &str, ins: &Instance) -> Result<String> {
+        Ok(append_para(para, &self.render(ins)?))
+    }
+}
+
+impl Instance {
+    /// render the template in sys_context `para.dynamic` against this instance, `None` if there is no template.
+    pub fn dynamic_para(&self) -> Result<Option<String>> {
+        match self.sys().dynamic_para() {
+            None => Ok(None),
+            Some(template) => Ok(Some(ParaTemplate::from_str(template)?.render(self)?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::CONTEXT_DYNAMIC_PARA;
+
+    use super::*;
+
+    fn instance() -> Instance {
+        let mut ins = Instance::new("sale/order").unwrap();
+        ins.content = r#"{"date":"20200101","order":{"id":5,"paid":true,"tags":["a"],"path":"x/y"}}"#.to_string();
+        ins.context.insert("shop".to_string(), "s1".to_string());
+        ins.context.insert("a.b".to_string(), "dotted".to_string());
+        ins
+    }
+
+    fn render(template: &str) -> Result<String> {
+        ParaTemplate::from_str(template)?.render(&instance())
+    }
+
+    #[test]
+    fn render_test() {
+        assert_eq!(render("${context.shop}/${content.date}"), Ok("s1/20200101".to_string()));
+        assert_eq!(render("${content.order.id}-${content/order/paid}"), Ok("5-true".to_string()));
+        assert_eq!(render("p_${context.a.b}"), Ok("p_dotted".to_string()));
+        assert_eq!(render("fixed"), Ok("fixed".to_string()));
+        assert_eq!(render("$5{x}"), Ok("$5{x}".to_string()));
+    }
+
+    #[test]
+    fn parse_error_test() {
+        assert_eq!(ParaTemplate::from_str("${context.shop"), Err(NatureError::VerifyError("`}` missed in para template: ${context.shop".to_string())));
+        assert_eq!(ParaTemplate::from_str("${shop}"), Err(NatureError::VerifyError("unknown placeholder `${shop}` in para template: ${shop}".to_string())));
+        assert_eq!(ParaTemplate::from_str("${context.}").is_err(), true);
+        assert_eq!(ParaTemplate::from_str("${content.}").is_err(), true);
+        assert_eq!(ParaTemplate::from_str("a|${context.x}"), Err(NatureError::VerifyError("para template can't contain [|]: a|${context.x}".to_string())));
+        assert_eq!(ParaTemplate::from_str("${context.x}|b").is_err(), true);
+    }
+
+    #[test]
+    fn render_error_test() {
+        assert_eq!(render("${context.none}"), Err(NatureError::VerifyError("context [none] not found for para".to_string())));
+        assert_eq!(render("${content.none}").is_err(), true);
+        assert_eq!(render("${content.order.tags}").is_err(), true);
+        assert_eq!(render("${content.order.path}"), Err(NatureError::VerifyError("value [x/y] of [content/order/path] can't contain [/] or [|]".to_string())));
+    }
+
+    #[test]
+    fn dynamic_para_test() {
+        let mut ins = instance();
+        assert_eq!(ins.dynamic_para(), Ok(None));
+        ins.sys_mut().set_dynamic_para("${context.shop}/${content.date}").unwrap();
+        assert_eq!(ins.dynamic_para(), Ok(Some("s1/20200101".to_string())));
+        let t = ParaTemplate::from_str("${context.shop}").unwrap();
+        assert_eq!(t.append_to("a", &ins), Ok("a/s1".to_string()));
+        assert_eq!(t.append_to("", &ins), Ok("s1".to_string()));
+        // illegal template can't be set
+        assert_eq!(ins.sys_mut().set(CONTEXT_DYNAMIC_PARA, "${shop}").is_err(), true);
+    }
+}
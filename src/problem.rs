@@ -0,0 +1,213 @@
+use crate::{DetailedError, ErrorCode, ErrorKind, NatureError, Result};
+
+/// The media type of RFC 7807
+pub static PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+/// `Problem::problem_type` is this prefix followed by `ErrorCode::name`
+pub static PROBLEM_TYPE_PREFIX: &str = "urn:nature:error:";
+
+/// RFC 7807 problem details, the wire form of `DetailedError` between Nature and the executors.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    #[serde(default = "about_blank")]
+    pub problem_type: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub status: u16,
+    #[serde(default)]
+    pub detail: String,
+    /// the key of the `Instance` being processed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub instance: Option<String>,
+    /// extension member: `ErrorCode::number`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub code: Option<u16>,
+    /// extension member: meta string
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub meta: Option<String>,
+    /// extension member: task id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub task_id: Option<String>,
+    /// extension member: the source error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub cause: Option<Box<Problem>>,
+}
+
+fn about_blank() -> String {
+    "about:blank".to_string()
+}
+
+impl ErrorCode {
+    /// Verify → 400, Logical → 422, DaoDuplicated → 409, System → 500, Environment → 503,
+    /// except Timeout → 504 and HttpThrottled → 429
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ErrorCode::Timeout => 504,
+            ErrorCode::HttpThrottled => 429,
+            _ => match self.kind() {
+                ErrorKind::Verify => 400,
+                ErrorKind::Logical => 422,
+                ErrorKind::DaoDuplicated => 409,
+                ErrorKind::System => 500,
+                ErrorKind::Environment => 503,
+            }
+        }
+    }
+
+    /// The only table from http status to code, it's used when there is no code in the response.
+    /// 5xx can be retried, so does 408 and 429, other 4xx can't; an unexpected status is `Logical`.
+    pub fn from_http_status(status: u16) -> ErrorCode {
+        match status {
+            400 => ErrorCode::Verify,
+            409 => ErrorCode::DaoDuplicated,
+            422 => ErrorCode::Logical,
+            429 => ErrorCode::HttpThrottled,
+            408 | 504 => ErrorCode::Timeout,
+            401..=499 => ErrorCode::HttpClient,
+            500..=599 => ErrorCode::HttpServer,
+            _ => ErrorCode::Logical,
+        }
+    }
+}
+
+impl From<&DetailedError> for Problem {
+    fn from(e: &DetailedError) -> Self {
+        Problem {
+            problem_type: format!("{}{}", PROBLEM_TYPE_PREFIX, e.code.name()),
+            title: format!("{:?}Error", e.kind()),
+            status: e.code.http_status(),
+            detail: e.message.to_string(),
+            instance: e.instance.clone(),
+            code: Some(e.code.number()),
+            meta: e.meta.clone(),
+            task_id: e.task_id.clone(),
+            cause: e.source.as_ref().map(|s| Box::new(Problem::from(s.as_ref()))),
+        }
+    }
+}
+
+impl From<&NatureError> for Problem {
+    fn from(e: &NatureError) -> Self {
+        Problem::from(&DetailedError::from(e.clone()))
+    }
+}
+
+/// `code` is preferred, `status` is used if `code` is absent or unknown.
+impl From<Problem> for DetailedError {
+    fn from(p: Problem) -> Self {
+        let code = match p.code.and_then(ErrorCode::from_number) {
+            Some(code) => code,
+            None => ErrorCode::from_http_status(p.status)
+        };
+        DetailedError {
+            code,
+            message: p.detail,
+            meta: p.meta,
+            instance: p.instance,
+            task_id: p.task_id,
+            source: p.cause.map(|c| Box::new(DetailedError::from(*c))),
+        }
+    }
+}
+
+impl Problem {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse the response of an executor. If `body` is not a problem json,
+    /// the error is made by `status` and the `body` is used as the message.
+    pub fn parse(status: u16, body: &str) -> DetailedError {
+        match serde_json::from_str::<Problem>(body) {
+            Ok(mut p) => {
+                if p.status == 0 {
+                    p.status = status;
+                }
+                DetailedError::from(p)
+            }
+            Err(_) => DetailedError::new(ErrorCode::from_http_status(status), body)
+        }
+    }
+}
+
+impl DetailedError {
+    pub fn to_problem(&self) -> Problem {
+        Problem::from(self)
+    }
+}
+
+impl NatureError {
+    pub fn to_problem(&self) -> Problem {
+        Problem::from(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn status_test() {
+        assert_eq!(NatureError::VerifyError("".to_string()).to_problem().status, 400);
+        assert_eq!(NatureError::LogicalError("".to_string()).to_problem().status, 422);
+        assert_eq!(NatureError::DaoDuplicated("".to_string()).to_problem().status, 409);
+        assert_eq!(NatureError::SystemError("".to_string()).to_problem().status, 500);
+        assert_eq!(NatureError::EnvironmentError("".to_string()).to_problem().status, 503);
+        assert_eq!(DetailedError::new(ErrorCode::Timeout, "").to_problem().status, 504);
+        assert_eq!(DetailedError::new(ErrorCode::Json, "").to_problem().status, 400);
+    }
+
+    #[test]
+    fn simple_round_trip() {
+        let e = NatureError::DaoDuplicated("exists".to_string());
+        let json = e.to_problem().to_json().unwrap();
+        assert_eq!(json, r#"{"type":"urn:nature:error:dao_duplicated","title":"DaoDuplicatedError","status":409,"detail":"exists","code":3000}"#);
+        assert_eq!(NatureError::from(Problem::parse(409, &json)), e);
+    }
+
+    #[test]
+    fn detailed_round_trip() {
+        let e = DetailedError::new(ErrorCode::HttpServer, "down")
+            .with_meta("B:a:1")
+            .with_instance("B:a:1|1||0")
+            .with_task("t1")
+            .with_source(NatureError::VerifyError("bad".to_string()));
+        let json = e.to_problem().to_json().unwrap();
+        assert_eq!(json, r#"{"type":"urn:nature:error:http_server","title":"EnvironmentError","status":503,"detail":"down","instance":"B:a:1|1||0","code":5003,"meta":"B:a:1","task_id":"t1","cause":{"type":"urn:nature:error:verify","title":"VerifyError","status":400,"detail":"bad","code":1000}}"#);
+        assert_eq!(Problem::parse(503, &json), e);
+    }
+
+    #[test]
+    fn parse_foreign() {
+        // no code, use status
+        let e = Problem::parse(0, r#"{"title":"Conflict","status":409,"detail":"dup"}"#);
+        assert_eq!(NatureError::from(e), NatureError::DaoDuplicated("dup".to_string()));
+        let e = Problem::parse(429, r#"{"detail":"slow down"}"#);
+        assert_eq!(e.code, ErrorCode::HttpThrottled);
+        assert_eq!(e.is_retryable(), true);
+        // unknown code
+        let e = Problem::parse(0, r#"{"status":400,"detail":"x","code":1999}"#);
+        assert_eq!(NatureError::from(e), NatureError::VerifyError("x".to_string()));
+        // no code, a 5xx status can be retried
+        let e = Problem::parse(500, r#"{"status":500,"detail":"oops"}"#);
+        assert_eq!(e.code, ErrorCode::HttpServer);
+        assert_eq!(e.is_retryable(), true);
+        // not a problem json
+        let e = Problem::parse(500, "oops");
+        assert_eq!(e.code, ErrorCode::HttpServer);
+        assert_eq!(e.is_retryable(), true);
+        assert_eq!(Problem::parse(503, "busy").is_retryable(), true);
+        assert_eq!(Problem::parse(200, "").is_retryable(), false);
+        assert_eq!(Problem::parse(302, "").code, ErrorCode::Logical);
+        let e = Problem::parse(404, "not found");
+        assert_eq!(e.code, ErrorCode::HttpClient);
+        assert_eq!(e.message, "not found");
+        assert_eq!(e.is_retryable(), false);
+    }
+}
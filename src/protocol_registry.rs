@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use futures::future::BoxFuture;
+
+use crate::{ConverterParameter, ConverterReturned, Executor, NatureError, Protocol, Result};
+
+/// Serve the `Executor`s of a `Protocol::Custom`
+pub trait ProtocolInvoker: Send + Sync {
+    fn invoke(&self, executor: &Executor, para: ConverterParameter) -> BoxFuture<'static, Result<ConverterReturned>>;
+}
+
+lazy_static! {
+    static ref PROTOCOLS: RwLock<HashMap<String, Arc<dyn ProtocolInvoker>>> = RwLock::new(HashMap::new());
+}
+
+/// `name` is case insensitive, the predefined protocols can't be registered.
+/// the former invoker of the same name will be replaced.
+pub fn register_protocol(name: &str, invoker: Arc<dyn ProtocolInvoker>) -> Result<()> {
+    match Protocol::from_str(name)? {
+        Protocol::Custom(name) => {
+            PROTOCOLS.write().unwrap().insert(name, invoker);
+            Ok(())
+        }
+        other => Err(NatureError::VerifyError(format!("predefined protocol can't be registered: {}", other)))
+    }
+}
+
+pub fn unregister_protocol(name: &str) -> Option<Arc<dyn ProtocolInvoker>> {
+    PROTOCOLS.write().unwrap().remove(&name.to_lowercase())
+}
+
+pub fn protocol_invoker(name: &str) -> Option<Arc<dyn ProtocolInvoker>> {
+    PROTOCOLS.read().unwrap().get(&name.to_lowercase()).cloned()
+}
+
+impl Executor {
+    /// call the registered invoker of `Protocol::Custom`
+    pub async fn call_custom(&self, para: ConverterParameter) -> Result<ConverterReturned> {
+        let name = match &self.protocol {
+            Protocol::Custom(name) => name,
+            other => return Err(NatureError::VerifyError(format!("{} is not a custom protocol", other)))
+        };
+        let invoker = match protocol_invoker(name) {
+            Some(invoker) => invoker,
+            None => return Err(NatureError::VerifyError(format!("unregistered protocol: {}", name)))
+        };
+        invoker.invoke(self, para).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::executor::block_on;
+    use futures::FutureExt;
+
+    use crate::Instance;
+
+    use super::*;
+
+    struct Echo;
+
+    impl ProtocolInvoker for Echo {
+        fn invoke(&self, executor: &Executor, para: ConverterParameter) -> BoxFuture<'static, Result<ConverterReturned>> {
+            let mut ins = para.from;
+            ins.content = executor.url.to_string();
+            async move { Ok(ConverterReturned::Instances(vec![ins])) }.boxed()
+        }
+    }
+
+    fn para() -> ConverterParameter {
+        ConverterParameter {
+            from: Instance::new("a").unwrap(),
+            last_state: None,
+            task_id: "".to_string(),
+            master: None,
+            cfg: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn register_test() {
+        assert_eq!(register_protocol("Echo", Arc::new(Echo)), Ok(()));
+        assert_eq!(protocol_invoker("echo").is_some(), true);
+        assert_eq!(register_protocol("http", Arc::new(Echo)), Err(NatureError::VerifyError("predefined protocol can't be registered: http".to_string())));
+        assert_eq!(register_protocol("", Arc::new(Echo)).is_err(), true);
+
+        let exe: Executor = serde_json::from_str(r#"{"protocol":"ECHO","url":"hello"}"#).unwrap();
+        match block_on(exe.call_custom(para())).unwrap() {
+            ConverterReturned::Instances(ins) => assert_eq!(ins[0].content, "hello"),
+            _ => panic!("should return instances")
+        }
+        assert_eq!(unregister_protocol("echo").is_some(), true);
+        assert_eq!(block_on(exe.call_custom(para())), Err(NatureError::VerifyError("unregistered protocol: echo".to_string())));
+        assert_eq!(block_on(Executor::new_auto().call_custom(para())).is_err(), true);
+    }
+
+    #[test]
+    fn verify_settings_test() {
+        let mut exe = Executor { protocol: Protocol::from_str("htp").unwrap(), url: "x".to_string(), settings: "".to_string() };
+        assert_eq!(exe.verify_settings(), Err(NatureError::VerifyError("unregistered protocol: htp".to_string())));
+        register_protocol("verified", Arc::new(Echo)).unwrap();
+        exe.protocol = Protocol::from_str("Verified").unwrap();
+        assert_eq!(exe.verify_settings(), Ok(()));
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt::Write;
+
+use futures::Future;
+
+use crate::{Instance, is_default, KeyCondition, Result};
+
+/// Where an `Instance` come from, built by walking the `from` chain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Provenance {
+    /// begin with the traced instance itself and end with the root
+    pub lineage: Vec<Instance>,
+    /// true if stopped by `max_depth` before reaching the root
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+impl Provenance {
+    /// walk the `from` chain to the root by `Instance::lineage_async`, at most `max_depth` upstream instances will be loaded.
+    /// `dao` is used to load the upstream instance, just like `Instance::get_master`
+    pub async fn trace<F, D>(instance: &Instance, max_depth: usize, dao: D) -> Result<Provenance>
+        where F: Future<Output=Result<Option<Instance>>>,
+              D: Fn(KeyCondition) -> F
+    {
+        let mut lineage = vec![instance.clone()];
+        lineage.append(&mut instance.lineage_async(max_depth, dao).await?);
+        let truncated = matches!(lineage.last(), Some(last) if last.from.is_some());
+        Ok(Provenance { lineage, truncated })
+    }
+
+    /// Graphviz DOT text, the edge direction is from upstream to downstream
+    pub fn to_dot(&self) -> String {
+        let mut rtn = "digraph provenance {\n    rankdir=LR;\n    node [shape=box];\n".to_string();
+        for (i, one) in self.lineage.iter().enumerate() {
+            let _ = writeln!(&mut rtn, "    n{} [label=\"{}\"];", i, one.get_key().replace('"', "\\\""));
+        }
+        for i in 1..self.lineage.len() {
+            let _ = writeln!(&mut rtn, "    n{} -> n{};", i, i - 1);
+        }
+        rtn.push_str("}\n");
+        rtn
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use futures::executor::block_on;
+    use futures::future::ready;
+
+    use crate::{FromInstance, ID, NatureError};
+
+    use super::*;
+
+    fn chain(len: usize) -> HashMap<String, Instance> {
+        let mut rtn: HashMap<String, Instance> = HashMap::new();
+        let mut upstream: Option<FromInstance> = None;
+        for i in 0..len {
+            let mut ins = Instance::new(&format!("n{}", i)).unwrap();
+            ins.id = i as ID + 1;
+            ins.from = upstream;
+            upstream = Some(FromInstance::from(&ins));
+            rtn.insert(KeyCondition::from(&ins).get_key(), ins);
+        }
+        rtn
+    }
+
+    fn last(map: &HashMap<String, Instance>) -> Instance {
+        let meta = format!("B:n{}:1", map.len() - 1);
+        map.values().find(|one| one.meta == meta).unwrap().clone()
+    }
+
+    #[test]
+    fn trace_test() {
+        let map = chain(3);
+        let dao = |c: KeyCondition| ready(Ok(map.get(&c.get_key()).cloned()));
+        let rtn = block_on(Provenance::trace(&last(&map), 10, dao)).unwrap();
+        let metas: Vec<&str> = rtn.lineage.iter().map(|one| one.meta.as_str()).collect();
+        assert_eq!(metas, vec!["B:n2:1", "B:n1:1", "B:n0:1"]);
+        assert_eq!(rtn.truncated, false);
+        assert_eq!(rtn.to_dot(), r#"digraph provenance {
+    rankdir=LR;
+    node [shape=box];
+    n0 [label="B:n2:1|3||0"];
+    n1 [label="B:n1:1|2||0"];
+    n2 [label="B:n0:1|1||0"];
+    n1 -> n0;
+    n2 -> n1;
+}
+"#);
+        let json = rtn.to_json().unwrap();
+        let back: Provenance = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, rtn);
+    }
+
+    #[test]
+    fn max_depth_test() {
+        let map = chain(5);
+        let dao = |c: KeyCondition| ready(Ok(map.get(&c.get_key()).cloned()));
+        let rtn = block_on(Provenance::trace(&last(&map), 2, dao)).unwrap();
+        assert_eq!(rtn.lineage.len(), 3);
+        assert_eq!(rtn.truncated, true);
+        let rtn = block_on(Provenance::trace(&last(&map), 4, dao)).unwrap();
+        assert_eq!(rtn.lineage.len(), 5);
+        assert_eq!(rtn.truncated, false);
+    }
+
+    #[test]
+    fn cycle_and_missing_test() {
+        let mut a = Instance::new("a").unwrap();
+        a.id = 1;
+        let mut b = Instance::new("b").unwrap();
+        b.id = 2;
+        b.from = Some(FromInstance::from(&a));
+        a.from = Some(FromInstance::from(&b));
+        let copy = b.clone();
+        let dao = |_: KeyCondition| ready(Ok(Some(copy.clone())));
+        let rtn = block_on(Provenance::trace(&a, 10, dao));
+        assert_eq!(rtn, Err(NatureError::VerifyError("cycle found in lineage at: B:a:1|1||0".to_string())));
+
+        let dao = |_: KeyCondition| ready(Ok(None));
+        let rtn = block_on(Provenance::trace(&b, 10, dao));
+        assert_eq!(rtn, Err(NatureError::VerifyError("upstream instance not found: B:a:1|1||0".to_string())));
+    }
+}
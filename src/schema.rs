@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::{Instance, MetaSetting, NatureError, Result};
+
+/// The JSON Schema used by `MetaSetting`.
+/// In the setting an object means inline schema, a string means a reference which will be loaded by the caller.
+#[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd)]
+pub enum SchemaSetting {
+    /// the json text of the schema
+    Inline(String),
+    Refer(String),
+}
+
+impl Serialize for SchemaSetting {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::Error;
+        match self {
+            SchemaSetting::Refer(refer) => serializer.serialize_str(refer),
+            SchemaSetting::Inline(json) => {
+                let value: Value = serde_json::from_str(json).map_err(S::Error::custom)?;
+                value.serialize(serializer)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SchemaSetting {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+        match Value::deserialize(deserializer)? {
+            Value::String(refer) => Ok(SchemaSetting::Refer(refer)),
+            value @ Value::Object(_) | value @ Value::Bool(_) => Ok(SchemaSetting::Inline(value.to_string())),
+            _ => Err(D::Error::custom("schema should be an object or a reference string")),
+        }
+    }
+}
+
+impl SchemaSetting {
+    /// `loader` return the json text of the referenced schema.
+    pub fn load<F>(&self, loader: &F) -> Result<Value>
+        where F: Fn(&str) -> Result<String>
+    {
+        let json = match self {
+            SchemaSetting::Inline(json) => json.to_string(),
+            SchemaSetting::Refer(refer) => loader(refer)?,
+        };
+        let schema: Value = serde_json::from_str(&json)?;
+        check_keywords(&schema, "$")?;
+        Ok(schema)
+    }
+
+    /// the referenced schema can only be checked when it's loaded
+    pub fn verify(&self) -> Result<()> {
+        match self {
+            SchemaSetting::Inline(json) => check_keywords(&serde_json::from_str(json)?, "$"),
+            SchemaSetting::Refer(_) => Ok(()),
+        }
+    }
+}
+
+/// the keywords `validate_json` can check
+static VALIDATION_KEYWORDS: &[&str] = &["type", "enum", "const", "properties", "required", "additionalProperties", "items",
+    "minItems", "maxItems", "minLength", "maxLength", "minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum",
+    "allOf", "anyOf", "oneOf", "not"];
+/// the keywords which have nothing to do with validation
+static ANNOTATION_KEYWORDS: &[&str] = &["$schema", "$id", "$comment", "title", "description", "default", "examples"];
+
+/// Reject the schema which uses unsupported keywords, or it would always pass for them.
+fn check_keywords(schema: &Value, path: &str) -> Result<()> {
+    let map = match schema {
+        Value::Object(map) => map,
+        Value::Bool(_) => return Ok(()),
+        _ => return Err(NatureError::VerifyError(format!("schema at {} should be an object or a bool", path)))
+    };
+    for (k, v) in map {
+        if ANNOTATION_KEYWORDS.contains(&k.as_str()) {
+            continue;
+        }
+        if !VALIDATION_KEYWORDS.contains(&k.as_str()) {
+            return Err(NatureError::VerifyError(format!("unsupported schema keyword [{}] at {}", k, path)));
+        }
+        let sub_path = format!("{}.{}", path, k);
+        match (k.as_str(), v) {
+            ("properties", Value::Object(properties)) => {
+                for (name, sub) in properties {
+                    check_keywords(sub, &format!("{}.{}", sub_path, name))?;
+                }
+            }
+            ("allOf", Value::Array(list)) | ("anyOf", Value::Array(list)) | ("oneOf", Value::Array(list)) => {
+                for (i, sub) in list.iter().enumerate() {
+                    check_keywords(sub, &format!("{}[{}]", sub_path, i))?;
+                }
+            }
+            ("additionalProperties", sub) | ("items", sub) | ("not", sub) => check_keywords(sub, &sub_path)?,
+            _ => ()
+        }
+    }
+    Ok(())
+}
+
+/// A place where the json does not match the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// such as `$.items[0].price`
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for SchemaViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Validate `data` by `schema`, supported keywords:
+/// type, enum, const, properties, required, additionalProperties, items, minItems, maxItems,
+/// minLength, maxLength, minimum, maximum, exclusiveMinimum, exclusiveMaximum, allOf, anyOf, oneOf, not.
+/// Other keywords are ignored here, but the schema used by `MetaSetting` can't contain them.
+pub fn validate_json(schema: &Value, data: &Value) -> Vec<SchemaViolation> {
+    let mut rtn: Vec<SchemaViolation> = vec![];
+    check(schema, data, "$", &mut rtn);
+    rtn
+}
+
+fn violate(rtn: &mut Vec<SchemaViolation>, path: &str, message: String) {
+    rtn.push(SchemaViolation { path: path.to_string(), message });
+}
+
+fn type_of(data: &Value) -> &'static str {
+    match data {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn is_type(data: &Value, name: &str) -> bool {
+    match name {
+        "integer" => match data {
+            Value::Number(n) => n.is_i64() || n.is_u64() || matches!(n.as_f64(), Some(f) if f.fract() == 0.0),
+            _ => false
+        },
+        _ => type_of(data) == name
+    }
+}
+
+fn check(schema: &Value, data: &Value, path: &str, rtn: &mut Vec<SchemaViolation>) {
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => return violate(rtn, path, "not allowed".to_string()),
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+    if let Some(t) = schema.get("type") {
+        let types: Vec<&str> = match t {
+            Value::String(one) => vec![one],
+            Value::Array(list) => list.iter().filter_map(|one| one.as_str()).collect(),
+            _ => vec![]
+        };
+        if !types.is_empty() && !types.iter().any(|one| is_type(data, one)) {
+            return violate(rtn, path, format!("should be {}, but get {}", types.join(" or "), type_of(data)));
+        }
+    }
+    if let Some(Value::Array(list)) = schema.get("enum") {
+        if !list.contains(data) {
+            violate(rtn, path, format!("should be one of {}", Value::Array(list.clone())));
+        }
+    }
+    if let Some(c) = schema.get("const") {
+        if c != data {
+            violate(rtn, path, format!("should be {}", c));
+        }
+    }
+    match data {
+        Value::Object(map) => check_object(schema, map, path, rtn),
+        Value::Array(list) => check_array(schema, list, path, rtn),
+        Value::String(s) => check_string(schema, s, path, rtn),
+        Value::Number(n) => if let Some(n) = n.as_f64() { check_number(schema, n, path, rtn) },
+        _ => (),
+    }
+    check_combination(schema, data, path, rtn);
+}
+
+fn check_object(schema: &Map<String, Value>, map: &Map<String, Value>, path: &str, rtn: &mut Vec<SchemaViolation>) {
+    if let Some(Value::Array(required)) = schema.get("required") {
+        required.iter().filter_map(|one| one.as_str())
+            .filter(|one| !map.contains_key(*one))
+            .for_each(|one| violate(rtn, path, format!("missing required property [{}]", one)));
+    }
+    let properties = match schema.get("properties") {
+        Some(Value::Object(p)) => Some(p),
+        _ => None
+    };
+    for (k, v) in map {
+        let sub_path = format!("{}.{}", path, k);
+        match properties.and_then(|p| p.get(k)) {
+            Some(sub) => check(sub, v, &sub_path, rtn),
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => violate(rtn, &sub_path, "additional property not allowed".to_string()),
+                Some(sub) => check(sub, v, &sub_path, rtn),
+                None => ()
+            }
+        }
+    }
+}
+
+fn check_array(schema: &Map<String, Value>, list: &[Value], path: &str, rtn: &mut Vec<SchemaViolation>) {
+    if let Some(min) = schema.get("minItems").and_then(|v| v.as_u64()) {
+        if (list.len() as u64) < min {
+            violate(rtn, path, format!("should have at least {} items", min));
+        }
+    }
+    if let Some(max) = schema.get("maxItems").and_then(|v| v.as_u64()) {
+        if list.len() as u64 > max {
+            violate(rtn, path, format!("should have at most {} items", max));
+        }
+    }
+    if let Some(items) = schema.get("items") {
+        for (i, one) in list.iter().enumerate() {
+            check(items, one, &format!("{}[{}]", path, i), rtn);
+        }
+    }
+}
+
+fn check_string(schema: &Map<String, Value>, s: &str, path: &str, rtn: &mut Vec<SchemaViolation>) {
+    let len = s.chars().count() as u64;
+    if let Some(min) = schema.get("minLength").and_then(|v| v.as_u64()) {
+        if len < min {
+            violate(rtn, path, format!("length should be at least {}", min));
+        }
+    }
+    if let Some(max) = schema.get("maxLength").and_then(|v| v.as_u64()) {
+        if len > max {
+            violate(rtn, path, format!("length should be at most {}", max));
+        }
+    }
+}
+
+fn check_number(schema: &Map<String, Value>, n: f64, path: &str, rtn: &mut Vec<SchemaViolation>) {
+    if let Some(min) = schema.get("minimum").and_then(|v| v.as_f64()) {
+        if n < min {
+            violate(rtn, path, format!("should be >= {}", min));
+        }
+    }
+    if let Some(max) = schema.get("maximum").and_then(|v| v.as_f64()) {
+        if n > max {
+            violate(rtn, path, format!("should be <= {}", max));
+        }
+    }
+    if let Some(min) = schema.get("exclusiveMinimum").and_then(|v| v.as_f64()) {
+        if n <= min {
+            violate(rtn, path, format!("should be > {}", min));
+        }
+    }
+    if let Some(max) = schema.get("exclusiveMaximum").and_then(|v| v.as_f64()) {
+        if n >= max {
+            violate(rtn, path, format!("should be < {}", max));
+        }
+    }
+}
+
+fn check_combination(schema: &Map<String, Value>, data: &Value, path: &str, rtn: &mut Vec<SchemaViolation>) {
+    if let Some(Value::Array(all)) = schema.get("allOf") {
+        all.iter().for_each(|one| check(one, data, path, rtn));
+    }
+    if let Some(Value::Array(any)) = schema.get("anyOf") {
+        if !any.iter().any(|one| validate_at(one, data, path).is_empty()) {
+            violate(rtn, path, "should match at least one schema of anyOf".to_string());
+        }
+    }
+    if let Some(Value::Array(one_of)) = schema.get("oneOf") {
+        let matched = one_of.iter().filter(|one| validate_at(one, data, path).is_empty()).count();
+        if matched != 1 {
+            violate(rtn, path, format!("should match exactly one schema of oneOf, but matched {}", matched));
+        }
+    }
+    if let Some(not) = schema.get("not") {
+        if validate_at(not, data, path).is_empty() {
+            violate(rtn, path, "should not match the schema of not".to_string());
+        }
+    }
+}
+
+fn validate_at(schema: &Value, data: &Value, path: &str) -> Vec<SchemaViolation> {
+    let mut rtn: Vec<SchemaViolation> = vec![];
+    check(schema, data, path, &mut rtn);
+    rtn
+}
+
+fn to_error(what: &str, instance: &Instance, violations: Vec<SchemaViolation>) -> Result<()> {
+    if violations.is_empty() {
+        return Ok(());
+    }
+    let msg = format!("{} of [{}] violates schema: {}", what, instance.meta, violations.iter().join("; "));
+    warn!("{}", &msg);
+    Err(NatureError::VerifyError(msg))
+}
+
+impl MetaSetting {
+    /// Check the `content` and `context` of the instance by the schemas defined in this setting.
+    /// `loader` is used to get the json text of the referenced schema.
+    pub fn check_schema<F>(&self, instance: &Instance, loader: F) -> Result<()>
+        where F: Fn(&str) -> Result<String>
+    {
+        if let Some(schema) = &self.content_schema {
+            let schema = schema.load(&loader)?;
+            let violations = match serde_json::from_str::<Value>(&instance.content) {
+                Ok(content) => validate_json(&schema, &content),
+                Err(e) => vec![SchemaViolation { path: "$".to_string(), message: format!("invalid json: {}", e) }]
+            };
+            to_error("content", instance, violations)?;
+        }
+        if let Some(schema) = &self.context_schema {
+            let schema = schema.load(&loader)?;
+            let context: &HashMap<String, String> = &instance.context;
+            let violations = validate_json(&schema, &serde_json::to_value(context)?);
+            to_error("context", instance, violations)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn no_refer(refer: &str) -> Result<String> {
+        Err(NatureError::VerifyError(format!("unknown schema: {}", refer)))
+    }
+
+    #[test]
+    fn setting_serde() {
+        let json = r#"{"content_schema":{"type":"object"},"context_schema":"order-context"}"#;
+        let setting = MetaSetting::from_str(json).unwrap();
+        assert_eq!(setting.content_schema, Some(SchemaSetting::Inline(r#"{"type":"object"}"#.to_string())));
+        assert_eq!(setting.context_schema, Some(SchemaSetting::Refer("order-context".to_string())));
+        assert_eq!(setting.to_json().unwrap(), json);
+        assert_eq!(MetaSetting::from_str(r#"{"content_schema":1}"#).is_err(), true);
+    }
+
+    #[test]
+    fn unsupported_keyword() {
+        let rtn = MetaSetting::from_str(r#"{"content_schema":{"title":"order","properties":{"id":{"type":"string","pattern":"^a"}}}}"#);
+        assert_eq!(rtn, Err(NatureError::VerifyError("unsupported schema keyword [pattern] at $.properties.id".to_string())));
+        assert_eq!(MetaSetting::from_str(r##"{"content_schema":{"$ref":"#/definitions/a"}}"##).is_err(), true);
+        assert_eq!(MetaSetting::from_str(r#"{"content_schema":{"items":{"uniqueItems":true}}}"#).is_err(), true);
+        assert_eq!(MetaSetting::from_str(r#"{"content_schema":{"anyOf":[true,{"format":"date"}]}}"#).is_err(), true);
+        // the referenced one is checked when loaded
+        let setting = MetaSetting::from_str(r#"{"content_schema":"order"}"#).unwrap();
+        let loader = |_: &str| Ok(r#"{"multipleOf":2}"#.to_string());
+        assert_eq!(setting.check_schema(&Instance::new("order").unwrap(), loader), Err(NatureError::VerifyError("unsupported schema keyword [multipleOf] at $".to_string())));
+    }
+
+    #[test]
+    fn validate() {
+        let schema: Value = serde_json::from_str(r#"{
+            "type":"object",
+            "required":["id","items"],
+            "additionalProperties":false,
+            "properties":{
+                "id":{"type":"integer","minimum":1},
+                "status":{"enum":["new","paid"]},
+                "items":{"type":"array","minItems":1,"items":{
+                    "type":"object",
+                    "properties":{"name":{"type":"string","minLength":1},"price":{"type":"number","exclusiveMinimum":0}}
+                }}
+            }
+        }"#).unwrap();
+        let data: Value = serde_json::from_str(r#"{"id":1,"status":"new","items":[{"name":"a","price":1.5}]}"#).unwrap();
+        assert_eq!(validate_json(&schema, &data), vec![]);
+        let data: Value = serde_json::from_str(r#"{"id":0.5,"status":"x","items":[{"name":"","price":0}],"memo":1}"#).unwrap();
+        let rtn: Vec<String> = validate_json(&schema, &data).iter().map(|v| v.to_string()).collect();
+        assert_eq!(rtn, vec![
+            "$.id: should be integer, but get number",
+            "$.items[0].name: length should be at least 1",
+            "$.items[0].price: should be > 0",
+            "$.memo: additional property not allowed",
+            "$.status: should be one of [\"new\",\"paid\"]",
+        ]);
+        let data: Value = serde_json::from_str(r#"{"items":[]}"#).unwrap();
+        let rtn: Vec<String> = validate_json(&schema, &data).iter().map(|v| v.to_string()).collect();
+        assert_eq!(rtn, vec!["$: missing required property [id]", "$.items: should have at least 1 items"]);
+    }
+
+    #[test]
+    fn combination() {
+        let schema: Value = serde_json::from_str(r#"{"anyOf":[{"type":"string"},{"type":"integer"}],"not":{"const":3}}"#).unwrap();
+        assert_eq!(validate_json(&schema, &Value::from("a")), vec![]);
+        assert_eq!(validate_json(&schema, &Value::from(2)), vec![]);
+        assert_eq!(validate_json(&schema, &Value::from(3)).len(), 1);
+        assert_eq!(validate_json(&schema, &Value::from(1.5)).len(), 1);
+        let schema: Value = serde_json::from_str(r#"{"oneOf":[{"type":"number"},{"type":"integer"}]}"#).unwrap();
+        assert_eq!(validate_json(&schema, &Value::from(1.5)), vec![]);
+        assert_eq!(validate_json(&schema, &Value::from(1)).len(), 1);
+    }
+
+    #[test]
+    fn check_instance() {
+        let setting = MetaSetting::from_str(r#"{"content_schema":{"type":"object","required":["id"]},"context_schema":"ctx"}"#).unwrap();
+        let mut ins = Instance::new("order").unwrap();
+        ins.content = r#"{"id":1}"#.to_string();
+        ins.context.insert("shop".to_string(), "s1".to_string());
+        let loader = |_: &str| Ok(r#"{"required":["shop"]}"#.to_string());
+        assert_eq!(setting.check_schema(&ins, loader), Ok(()));
+        assert_eq!(setting.check_schema(&ins, no_refer), Err(NatureError::VerifyError("unknown schema: ctx".to_string())));
+
+        ins.content = r#"{"no":1}"#.to_string();
+        assert_eq!(setting.check_schema(&ins, loader), Err(NatureError::VerifyError("content of [B:order:1] violates schema: $: missing required property [id]".to_string())));
+        ins.content = "bad".to_string();
+        assert_eq!(setting.check_schema(&ins, loader).is_err(), true);
+
+        ins.content = r#"{"id":1}"#.to_string();
+        ins.context.clear();
+        assert_eq!(setting.check_schema(&ins, loader), Err(NatureError::VerifyError("context of [B:order:1] violates schema: $: missing required property [shop]".to_string())));
+    }
+}
@@ -13,6 +13,10 @@ lazy_static! {
     pub static ref SEPARATOR_META_KEY:String={
         env::var("SEPARATOR_META_KEY").unwrap_or_else(|_| "/".to_string())
     };
+    /// used by time-ordered id, each process should have a different one, it's checked by `snowflake_generator`.
+    pub static ref WORKER_ID:String={
+        env::var("WORKER_ID").unwrap_or_else(|_| "0".to_string())
+    };
 }
 
 /// This is only used for deserialize
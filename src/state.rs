@@ -1,6 +1,7 @@
 use std::fmt::Write;
 
 use crate::{NatureError, Result};
+use crate::state_parser::parse_states;
 
 /// It can't have the state with same name.
 pub type States = Vec<State>;
@@ -37,105 +38,14 @@ impl State {
         rtn
     }
 
+    /// Parse the states definition such as `a,p[b|c]`, the `usize` returned is the length of the input consumed.
+    /// Syntax errors are returned as `VerifyError` with the line and column where they occur.
     pub fn string_to_states(states: &str) -> Result<(States, usize)> {
         // check length
-        if states.len() < 1 {
+        if states.trim().is_empty() {
             return Err(NatureError::VerifyError("states string should not be empty".to_string()));
         }
-        // store temp result
-        let mut rtn: States = vec![];
-        let mut normal = String::new();
-        let mut mutex: States = vec![];
-        let mut is_mutex = false;
-        let mut parent: Option<State> = None;
-        let mut x = 0;
-        // main progress
-        while x < states.len() {
-            let c = &states[x..x + 1];
-            match c {
-                "," => {    // separator
-                    if is_mutex {
-                        if normal.len() > 0 {
-                            mutex.push(State::Normal(normal));
-                            normal = String::new();
-                        } else if parent.is_some() {
-                            mutex.push(parent.unwrap());
-                            parent = None;
-                        }
-                        let mut nm: States = vec![];
-                        nm.append(&mut mutex);
-                        rtn.push(State::Mutex(nm));
-                        is_mutex = false;
-                    } else {
-                        if normal.len() > 0 {  // the ']' logic will make `normal` be empty.
-                            rtn.push(State::Normal(normal));
-                            normal = String::new();
-                        } else if parent.is_some() {
-                            rtn.push(parent.unwrap());
-                            parent = None;
-                        }
-                    }
-                }
-                "|" => {    // mutex
-                    is_mutex = true;
-                    if normal.len() > 0 {  // the ']' logic will make `normal` be empty.
-                        mutex.push(State::Normal(normal));
-                        normal = String::new();
-                    } else if parent.is_some() {
-                        mutex.push(parent.unwrap());
-                        parent = None;
-                    }
-                }
-                "[" => {    // parent begin
-                    let r = Self::string_to_states(&states[x + 1..])?;
-                    x = x + r.1;
-                    parent = Some(State::Parent(normal, r.0));
-                    normal = String::new();
-                }
-                "]" => {    // parent end
-                    match is_mutex {
-                        false => {
-                            if normal.len() > 0 {
-                                rtn.push(State::Normal(normal));
-                            } else if parent.is_some() {
-                                rtn.push(parent.unwrap());
-                            }
-                        }
-                        true => {
-                            if normal.len() > 0 {
-                                mutex.push(State::Normal(normal))
-                            } else if parent.is_some() {
-                                mutex.push(parent.unwrap());
-                            }
-                            rtn.push(State::Mutex(mutex));
-                        }
-                    }
-                    return Ok((rtn, x + 1));
-                }
-                _ => {      // litera
-                    let w = write!(&mut normal, "{}", c);
-                    if w.is_err() {
-                        return Err(NatureError::SystemError(w.err().unwrap().to_string()));
-                    }
-                }
-            }
-            x = x + 1;
-        }
-        // the last normal unhandled by loop
-        if is_mutex {
-            if normal.len() > 0 {
-                mutex.push(State::Normal(normal));
-            } else if parent.is_some() {
-                mutex.push(parent.unwrap());
-            }
-            rtn.push(State::Mutex(mutex));
-        } else {
-            if normal.len() > 0 {
-                rtn.push(State::Normal(normal));
-            } else if parent.is_some() {
-                rtn.push(parent.unwrap());
-            }
-        }
+        let rtn = parse_states(states)?;
         Ok((rtn, states.len()))
     }
 
@@ -422,28 +332,26 @@ mod string_to_states_for_parent {
 
     #[test]
     fn comma_end() {
-        let rtn = State::string_to_states("p[a,").unwrap();
-        assert_eq!(rtn.0.len(), 1);
-        assert_eq!(rtn.0[0], State::Parent("p".to_string(), vec![State::Normal("a".to_string())]));
+        let rtn = State::string_to_states("p[a,");
+        assert_eq!(rtn, Err(NatureError::VerifyError("expected state name, found `end of input` at line 1, column 5".to_string())));
 
-        let rtn = State::string_to_states("p[a,b],").unwrap();
-        assert_eq!(rtn.0.len(), 1);
-        assert_eq!(rtn.0[0], State::Parent("p".to_string(), vec![
-            State::Normal("a".to_string()),
-            State::Normal("b".to_string())]));
+        let rtn = State::string_to_states("p[a,b],");
+        assert_eq!(rtn, Err(NatureError::VerifyError("expected state name, found `end of input` at line 1, column 8".to_string())));
     }
 
     #[test]
     fn right_square_missed() {
-        let rtn = State::string_to_states("p[a").unwrap();
-        assert_eq!(rtn.0.len(), 1);
-        assert_eq!(rtn.0[0], State::Parent("p".to_string(), vec![State::Normal("a".to_string())]));
+        let rtn = State::string_to_states("p[a");
+        assert_eq!(rtn, Err(NatureError::VerifyError("expected `]` to close parent [p], found `end of input` at line 1, column 4".to_string())));
 
-        let rtn = State::string_to_states("p[a,b").unwrap();
-        assert_eq!(rtn.0.len(), 1);
-        assert_eq!(rtn.0[0], State::Parent("p".to_string(), vec![
-            State::Normal("a".to_string()),
-            State::Normal("b".to_string())]));
+        let rtn = State::string_to_states("p[a,b");
+        assert_eq!(rtn, Err(NatureError::VerifyError("expected `]` to close parent [p], found `end of input` at line 1, column 6".to_string())));
+    }
+
+    #[test]
+    fn right_square_redundant() {
+        let rtn = State::string_to_states("a|b[c|d,e]]");
+        assert_eq!(rtn, Err(NatureError::VerifyError("expected `,`, `|` or end of input, found `]` at line 1, column 11".to_string())));
     }
 }
 
@@ -469,10 +377,14 @@ mod string_to_states_for_normal {
 
     #[test]
     fn comma_end() {
-        let rtn = State::string_to_states("a,b,").unwrap();
-        assert_eq!(rtn.0.len(), 2);
-        assert_eq!(rtn.0[0], State::Normal("a".to_string()));
-        assert_eq!(rtn.0[1], State::Normal("b".to_string()));
+        let rtn = State::string_to_states("a,b,");
+        assert_eq!(rtn, Err(NatureError::VerifyError("expected state name, found `end of input` at line 1, column 5".to_string())));
+    }
+
+    #[test]
+    fn empty() {
+        let rtn = State::string_to_states(" ");
+        assert_eq!(rtn, Err(NatureError::VerifyError("states string should not be empty".to_string())));
     }
 }
 
@@ -0,0 +1,169 @@
+use std::fmt::Write;
+
+use crate::{Result, State, States};
+
+/// Render `States` to diagram text, each state gets an id like `s1`, `s2`...,
+/// so that the same name in different place will not be merged.
+struct Diagram {
+    out: String,
+    id: u32,
+}
+
+impl Diagram {
+    fn new() -> Self {
+        Diagram { out: String::new(), id: 0 }
+    }
+
+    fn next_id(&mut self) -> String {
+        self.id += 1;
+        format!("s{}", self.id)
+    }
+
+    fn dot(&mut self, states: &[State], depth: usize) {
+        let indent = "    ".repeat(depth);
+        for one in states {
+            let id = self.next_id();
+            match one {
+                State::Normal(name) => {
+                    let _ = writeln!(&mut self.out, "{}{} [label=\"{}\"];", indent, id, dot_escape(name));
+                }
+                State::Parent(name, children) => {
+                    let _ = writeln!(&mut self.out, "{}subgraph cluster_{} {{", indent, id);
+                    let _ = writeln!(&mut self.out, "{}    label=\"{}\";", indent, dot_escape(name));
+                    self.dot(children, depth + 1);
+                    let _ = writeln!(&mut self.out, "{}}}", indent);
+                }
+                State::Mutex(children) => {
+                    let _ = writeln!(&mut self.out, "{}subgraph cluster_{} {{", indent, id);
+                    let _ = writeln!(&mut self.out, "{}    label=\"mutex\";", indent);
+                    let _ = writeln!(&mut self.out, "{}    style=dashed;", indent);
+                    self.dot(children, depth + 1);
+                    let _ = writeln!(&mut self.out, "{}}}", indent);
+                }
+            }
+        }
+    }
+
+    fn mermaid(&mut self, states: &[State], depth: usize) {
+        let indent = "    ".repeat(depth);
+        for one in states {
+            let id = self.next_id();
+            let (name, children) = match one {
+                State::Normal(name) => (mermaid_escape(name), None),
+                State::Parent(name, children) => (mermaid_escape(name), Some(children)),
+                State::Mutex(children) => ("mutex".to_string(), Some(children)),
+            };
+            let _ = writeln!(&mut self.out, "{}state \"{}\" as {}", indent, name, id);
+            if let Some(children) = children {
+                let _ = writeln!(&mut self.out, "{}state {} {{", indent, id);
+                self.mermaid(children, depth + 1);
+                let _ = writeln!(&mut self.out, "{}}}", indent);
+            }
+            // composite states can't be styled by Mermaid, so mark the mutex by a note
+            if let State::Mutex(_) = one {
+                let _ = writeln!(&mut self.out, "{}note right of {} : only one of the states", indent, id);
+            }
+        }
+    }
+}
+
+fn dot_escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn mermaid_escape(name: &str) -> String {
+    name.replace('"', "#quot;")
+}
+
+impl State {
+    /// Graphviz DOT text, parent and mutex are drawn as clusters, mutex cluster is dashed.
+    pub fn states_to_dot(states: &States) -> String {
+        let mut diagram = Diagram::new();
+        diagram.out.push_str("digraph states {\n");
+        diagram.out.push_str("    node [shape=box, style=rounded];\n");
+        diagram.dot(states, 1);
+        diagram.out.push_str("}\n");
+        diagram.out
+    }
+
+    /// Mermaid state-diagram text, parent and mutex are drawn as composite states, mutex has a note.
+    pub fn states_to_mermaid(states: &States) -> String {
+        let mut diagram = Diagram::new();
+        diagram.out.push_str("stateDiagram-v2\n");
+        diagram.mermaid(states, 1);
+        diagram.out
+    }
+
+    /// same as `states_to_dot` but from the string accepted by `string_to_states`
+    pub fn string_to_dot(states: &str) -> Result<String> {
+        let (ss, _) = Self::string_to_states(states)?;
+        Ok(Self::states_to_dot(&ss))
+    }
+
+    /// same as `states_to_mermaid` but from the string accepted by `string_to_states`
+    pub fn string_to_mermaid(states: &str) -> Result<String> {
+        let (ss, _) = Self::string_to_states(states)?;
+        Ok(Self::states_to_mermaid(&ss))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dot_test() {
+        let rtn = State::string_to_dot("a,p[b,c|d]").unwrap();
+        assert_eq!(rtn, r#"digraph states {
+    node [shape=box, style=rounded];
+    s1 [label="a"];
+    subgraph cluster_s2 {
+        label="p";
+        s3 [label="b"];
+        subgraph cluster_s4 {
+            label="mutex";
+            style=dashed;
+            s5 [label="c"];
+            s6 [label="d"];
+        }
+    }
+}
+"#);
+    }
+
+    #[test]
+    fn mermaid_test() {
+        let rtn = State::string_to_mermaid("a,p[b,c|d]").unwrap();
+        assert_eq!(rtn, r#"stateDiagram-v2
+    state "a" as s1
+    state "p" as s2
+    state s2 {
+        state "b" as s3
+        state "mutex" as s4
+        state s4 {
+            state "c" as s5
+            state "d" as s6
+        }
+        note right of s4 : only one of the states
+    }
+"#);
+    }
+
+    #[test]
+    fn same_name_and_escape() {
+        let rtn = State::states_to_dot(&vec![
+            State::Normal("a\"b".to_string()),
+            State::Parent("p".to_string(), vec![State::Normal("a\"b".to_string())]),
+        ]);
+        assert_eq!(rtn.contains(r#"s1 [label="a\"b"];"#), true);
+        assert_eq!(rtn.contains(r#"s3 [label="a\"b"];"#), true);
+        let rtn = State::states_to_mermaid(&vec![State::Normal("a\"b".to_string())]);
+        assert_eq!(rtn, "stateDiagram-v2\n    state \"a#quot;b\" as s1\n");
+    }
+
+    #[test]
+    fn syntax_error() {
+        assert_eq!(State::string_to_dot("a,").is_err(), true);
+        assert_eq!(State::string_to_mermaid("p[a").is_err(), true);
+    }
+}
@@ -0,0 +1,252 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::{NatureError, State, States};
+
+/// Tells where and why a states definition can't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateSyntaxError {
+    /// start from 1
+    pub line: usize,
+    /// start from 1, counted by char
+    pub column: usize,
+    /// the token which the parser can't accept
+    pub token: String,
+    pub message: String,
+}
+
+impl Display for StateSyntaxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, found `{}` at line {}, column {}", self.message, self.token, self.line, self.column)
+    }
+}
+
+impl From<StateSyntaxError> for NatureError {
+    fn from(e: StateSyntaxError) -> Self {
+        NatureError::VerifyError(e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    /// `,`
+    Separator,
+    /// `|`
+    Mutex,
+    /// `[`
+    ParentBegin,
+    /// `]`
+    ParentEnd,
+    End,
+}
+
+impl Token {
+    fn text(&self) -> String {
+        match self {
+            Token::Name(name) => name.to_string(),
+            Token::Separator => ",".to_string(),
+            Token::Mutex => "|".to_string(),
+            Token::ParentBegin => "[".to_string(),
+            Token::ParentEnd => "]".to_string(),
+            Token::End => "end of input".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Positioned {
+    token: Token,
+    line: usize,
+    column: usize,
+}
+
+/// Split the definition into tokens, white spaces between tokens are ignored.
+fn tokenize(input: &str) -> Vec<Positioned> {
+    let mut rtn: Vec<Positioned> = vec![];
+    let mut line = 1;
+    let mut column = 0;
+    let mut name = String::new();
+    let mut name_at = (0, 0);
+    for c in input.chars() {
+        column += 1;
+        let token = match c {
+            ',' => Some(Token::Separator),
+            '|' => Some(Token::Mutex),
+            '[' => Some(Token::ParentBegin),
+            ']' => Some(Token::ParentEnd),
+            _ if c.is_whitespace() => None,
+            _ => {
+                if name.is_empty() {
+                    name_at = (line, column);
+                }
+                name.push(c);
+                continue;
+            }
+        };
+        if !name.is_empty() {
+            rtn.push(Positioned { token: Token::Name(name), line: name_at.0, column: name_at.1 });
+            name = String::new();
+        }
+        match token {
+            Some(token) => rtn.push(Positioned { token, line, column }),
+            None => if c == '\n' {
+                line += 1;
+                column = 0;
+            }
+        }
+    }
+    if !name.is_empty() {
+        rtn.push(Positioned { token: Token::Name(name), line: name_at.0, column: name_at.1 });
+    }
+    rtn.push(Positioned { token: Token::End, line, column: column + 1 });
+    rtn
+}
+
+/// grammar:
+/// - list   : item (`,` item)*
+/// - item   : single (`|` single)*
+/// - single : name (`[` list `]`)?
+struct Parser {
+    tokens: Vec<Positioned>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Positioned {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Positioned {
+        let rtn = self.tokens[self.pos].clone();
+        if rtn.token != Token::End {
+            self.pos += 1;
+        }
+        rtn
+    }
+
+    fn error(at: &Positioned, message: &str) -> StateSyntaxError {
+        StateSyntaxError {
+            line: at.line,
+            column: at.column,
+            token: at.token.text(),
+            message: message.to_string(),
+        }
+    }
+
+    fn list(&mut self) -> Result<States, StateSyntaxError> {
+        let mut rtn: States = vec![self.item()?];
+        while self.peek().token == Token::Separator {
+            self.next();
+            rtn.push(self.item()?);
+        }
+        Ok(rtn)
+    }
+
+    fn item(&mut self) -> Result<State, StateSyntaxError> {
+        let first = self.single()?;
+        if self.peek().token != Token::Mutex {
+            return Ok(first);
+        }
+        let mut mutex: States = vec![first];
+        while self.peek().token == Token::Mutex {
+            self.next();
+            mutex.push(self.single()?);
+        }
+        Ok(State::Mutex(mutex))
+    }
+
+    fn single(&mut self) -> Result<State, StateSyntaxError> {
+        let name = match self.next() {
+            Positioned { token: Token::Name(name), .. } => name,
+            other => return Err(Self::error(&other, "expected state name")),
+        };
+        if self.peek().token != Token::ParentBegin {
+            return Ok(State::Normal(name));
+        }
+        self.next();
+        let children = self.list()?;
+        let end = self.next();
+        if end.token != Token::ParentEnd {
+            let msg = format!("expected `]` to close parent [{}]", name);
+            return Err(Self::error(&end, &msg));
+        }
+        Ok(State::Parent(name, children))
+    }
+}
+
+/// Parse a states definition such as `a,p[b|c]` into `States`.
+pub(crate) fn parse_states(input: &str) -> Result<States, StateSyntaxError> {
+    let mut parser = Parser { tokens: tokenize(input), pos: 0 };
+    let rtn = parser.list()?;
+    let end = parser.next();
+    if end.token != Token::End {
+        return Err(Parser::error(&end, "expected `,`, `|` or end of input"));
+    }
+    Ok(rtn)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn err(input: &str) -> (usize, usize, String) {
+        let e = parse_states(input).err().unwrap();
+        (e.line, e.column, e.token)
+    }
+
+    #[test]
+    fn unbalanced_square() {
+        assert_eq!(err("a|b[c|d,e]]"), (1, 11, "]".to_string()));
+        assert_eq!(err("p[a"), (1, 4, "end of input".to_string()));
+        assert_eq!(err("p[a,b|c"), (1, 8, "end of input".to_string()));
+        assert_eq!(err("]"), (1, 1, "]".to_string()));
+    }
+
+    #[test]
+    fn empty_name() {
+        assert_eq!(err("a,,b"), (1, 3, ",".to_string()));
+        assert_eq!(err("[a]"), (1, 1, "[".to_string()));
+        assert_eq!(err("p[]"), (1, 3, "]".to_string()));
+        assert_eq!(err("a||b"), (1, 3, "|".to_string()));
+    }
+
+    #[test]
+    fn trailing_separator() {
+        assert_eq!(err("a,b,"), (1, 5, "end of input".to_string()));
+        assert_eq!(err("a|"), (1, 3, "end of input".to_string()));
+        assert_eq!(err("p[a,]"), (1, 5, "]".to_string()));
+    }
+
+    #[test]
+    fn multi_line() {
+        let rtn = parse_states("a,\n  p[b |\n c]").unwrap();
+        assert_eq!(rtn, vec![
+            State::Normal("a".to_string()),
+            State::Parent("p".to_string(), vec![State::Mutex(vec![
+                State::Normal("b".to_string()),
+                State::Normal("c".to_string()),
+            ])]),
+        ]);
+        assert_eq!(err("a,\n  p[b c]"), (2, 7, "c".to_string()));
+    }
+
+    #[test]
+    fn non_ascii() {
+        let rtn = parse_states("新建,支付[成功|失败]").unwrap();
+        assert_eq!(rtn, vec![
+            State::Normal("新建".to_string()),
+            State::Parent("支付".to_string(), vec![State::Mutex(vec![
+                State::Normal("成功".to_string()),
+                State::Normal("失败".to_string()),
+            ])]),
+        ]);
+        assert_eq!(err("新建,,"), (1, 4, ",".to_string()));
+    }
+
+    #[test]
+    fn message() {
+        let e: NatureError = parse_states("p[a").err().unwrap().into();
+        assert_eq!(e, NatureError::VerifyError("expected `]` to close parent [p], found `end of input` at line 1, column 4".to_string()));
+    }
+}
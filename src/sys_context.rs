@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use crate::{BizObject, CONTEXT_DYNAMIC_PARA, CONTEXT_LOOP_FINISHED, CONTEXT_LOOP_ID, CONTEXT_LOOP_LEN, CONTEXT_LOOP_NEXT, CONTEXT_LOOP_TASK, CONTEXT_TARGET_INSTANCE_ID, CONTEXT_TARGET_INSTANCE_PARA, ID, id_from_hex_str, LoopContext, NatureError, ParaTemplate, Result, SEPARATOR_INS_KEY};
+
+/// check the value of a sys_context key
+pub type SysContextValidator = fn(&str) -> Result<()>;
+
+fn any_value(_: &str) -> Result<()> {
+    Ok(())
+}
+
+fn not_empty(value: &str) -> Result<()> {
+    match value.is_empty() {
+        true => Err(NatureError::VerifyError("value should not be empty".to_string())),
+        false => Ok(())
+    }
+}
+
+fn hex_id(value: &str) -> Result<()> {
+    id_from_hex_str(value).map(|_| ())
+}
+
+fn boolean(value: &str) -> Result<()> {
+    bool::from_str(value).map(|_| ()).map_err(|e| NatureError::VerifyError(e.to_string()))
+}
+
+fn number(value: &str) -> Result<()> {
+    usize::from_str(value).map(|_| ())?;
+    Ok(())
+}
+
+fn para_template(value: &str) -> Result<()> {
+    not_empty(value)?;
+    ParaTemplate::from_str(value).map(|_| ())
+}
+
+fn para(value: &str) -> Result<()> {
+    match value.contains(&*SEPARATOR_INS_KEY) {
+        true => Err(NatureError::VerifyError(format!("para can't contain [{}]", *SEPARATOR_INS_KEY))),
+        false => Ok(())
+    }
+}
+
+/// the prefixes of the predefined keys, an unknown key with them is treated as a typo.
+static RESERVED_PREFIXES: [&str; 3] = ["loop.", "target.", "para."];
+
+lazy_static! {
+    static ref SYS_CONTEXT_KEYS: RwLock<HashMap<String, SysContextValidator>> = {
+        let mut map: HashMap<String, SysContextValidator> = HashMap::new();
+        map.insert(CONTEXT_TARGET_INSTANCE_ID.to_string(), hex_id);
+        map.insert(CONTEXT_TARGET_INSTANCE_PARA.to_string(), para);
+        map.insert(CONTEXT_LOOP_NEXT.to_string(), any_value);
+        map.insert(CONTEXT_LOOP_ID.to_string(), not_empty);
+        map.insert(CONTEXT_LOOP_TASK.to_string(), not_empty);
+        map.insert(CONTEXT_LOOP_FINISHED.to_string(), boolean);
+        map.insert(CONTEXT_LOOP_LEN.to_string(), number);
+        map.insert(CONTEXT_DYNAMIC_PARA.to_string(), para_template);
+        RwLock::new(map)
+    };
+}
+
+/// Register a validator for a custom key, a key with a reserved prefix can be accepted only after registered.
+/// The predefined keys can't be replaced.
+pub fn register_sys_context_key(key: &str, validator: SysContextValidator) -> Result<()> {
+    if key.is_empty() {
+        return Err(NatureError::VerifyError("sys_context key should not be empty".to_string()));
+    }
+    let mut keys = SYS_CONTEXT_KEYS.write().unwrap();
+    let predefined = [CONTEXT_TARGET_INSTANCE_ID, CONTEXT_TARGET_INSTANCE_PARA, CONTEXT_LOOP_NEXT, CONTEXT_LOOP_ID,
+        CONTEXT_LOOP_TASK, CONTEXT_LOOP_FINISHED, CONTEXT_LOOP_LEN, CONTEXT_DYNAMIC_PARA];
+    if predefined.contains(&key) {
+        return Err(NatureError::VerifyError(format!("predefined sys_context key can't be registered: {}", key)));
+    }
+    keys.insert(key.to_string(), validator);
+    Ok(())
+}
+
+fn validate(key: &str, value: &str) -> Result<()> {
+    let validator = match SYS_CONTEXT_KEYS.read().unwrap().get(key) {
+        Some(v) => *v,
+        None => return match RESERVED_PREFIXES.iter().any(|p| key.starts_with(p)) {
+            true => Err(NatureError::VerifyError(format!("unknown sys_context key: {}", key))),
+            false => Ok(())
+        }
+    };
+    match validator(value) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(NatureError::VerifyError(format!("illegal value [{}] for sys_context [{}]: {}", value, key, e.message())))
+    }
+}
+
+/// A typed view of `BizObject::sys_context`
+#[derive(Debug, Clone, Copy)]
+pub struct SysContext<'a> {
+    map: &'a HashMap<String, String>,
+}
+
+impl<'a> SysContext<'a> {
+    pub fn new(map: &'a HashMap<String, String>) -> Self {
+        SysContext { map }
+    }
+
+    pub fn get<T: FromStr>(&self, key: &str) -> Result<Option<T>> {
+        match self.map.get(key) {
+            None => Ok(None),
+            Some(v) => match T::from_str(v) {
+                Ok(rtn) => Ok(Some(rtn)),
+                Err(_) => Err(NatureError::VerifyError(format!("illegal value [{}] for sys_context [{}]", v, key)))
+            }
+        }
+    }
+
+    pub fn target_id(&self) -> Result<Option<ID>> {
+        match self.map.get(CONTEXT_TARGET_INSTANCE_ID) {
+            None => Ok(None),
+            Some(v) => Ok(Some(id_from_hex_str(v)?))
+        }
+    }
+
+    pub fn target_para(&self) -> Option<&'a str> {
+        self.map.get(CONTEXT_TARGET_INSTANCE_PARA).map(|v| v.as_str())
+    }
+
+    pub fn loop_id(&self) -> Option<&'a str> {
+        self.map.get(CONTEXT_LOOP_ID).map(|v| v.as_str())
+    }
+
+    pub fn loop_task(&self) -> Option<&'a str> {
+        self.map.get(CONTEXT_LOOP_TASK).map(|v| v.as_str())
+    }
+
+    /// `None` if neither `loop.next` nor `loop.len` exists
+    pub fn loop_context(&self) -> Result<Option<LoopContext>> {
+        let next = self.map.get(CONTEXT_LOOP_NEXT);
+        let len: Option<usize> = self.get(CONTEXT_LOOP_LEN)?;
+        if next.is_none() && len.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(LoopContext {
+            next: next.cloned().unwrap_or_default(),
+            len: len.unwrap_or_default(),
+        }))
+    }
+
+    pub fn loop_finished(&self) -> Result<bool> {
+        Ok(self.get(CONTEXT_LOOP_FINISHED)?.unwrap_or(false))
+    }
+
+    /// the template of `ParaTemplate`
+    pub fn dynamic_para(&self) -> Option<&'a str> {
+        self.map.get(CONTEXT_DYNAMIC_PARA).map(|v| v.as_str())
+    }
+
+    /// the value of each predefined or registered key should be legal,
+    /// other keys are free to use, except the ones with a reserved prefix, e.g. `loop.`
+    pub fn verify(&self) -> Result<()> {
+        let mut keys: Vec<&String> = self.map.keys().collect();
+        keys.sort();
+        for key in keys {
+            validate(key, &self.map[key])?;
+        }
+        Ok(())
+    }
+}
+
+/// The mutable typed view of `BizObject::sys_context`, the values are validated when set.
+#[derive(Debug)]
+pub struct SysContextMut<'a> {
+    map: &'a mut HashMap<String, String>,
+}
+
+impl<'a> SysContextMut<'a> {
+    pub fn new(map: &'a mut HashMap<String, String>) -> Self {
+        SysContextMut { map }
+    }
+
+    pub fn view(&self) -> SysContext<'_> {
+        SysContext::new(self.map)
+    }
+
+    pub fn set<T: ToString>(&mut self, key: &str, value: T) -> Result<()> {
+        let value = value.to_string();
+        validate(key, &value)?;
+        self.map.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.map.remove(key)
+    }
+
+    pub fn set_target_id(&mut self, id: ID) {
+        self.map.insert(CONTEXT_TARGET_INSTANCE_ID.to_string(), format!("{:x}", id));
+    }
+
+    pub fn set_target_para(&mut self, para: &str) -> Result<()> {
+        self.set(CONTEXT_TARGET_INSTANCE_PARA, para)
+    }
+
+    pub fn set_loop_id(&mut self, id: &str) -> Result<()> {
+        self.set(CONTEXT_LOOP_ID, id)
+    }
+
+    pub fn set_loop_task(&mut self, task_id: &str) -> Result<()> {
+        self.set(CONTEXT_LOOP_TASK, task_id)
+    }
+
+    /// empty `next` will be removed
+    pub fn set_loop_context(&mut self, context: &LoopContext) {
+        match context.next.is_empty() {
+            true => self.map.remove(CONTEXT_LOOP_NEXT),
+            false => self.map.insert(CONTEXT_LOOP_NEXT.to_string(), context.next.to_string())
+        };
+        self.map.insert(CONTEXT_LOOP_LEN.to_string(), context.len.to_string());
+    }
+
+    pub fn set_loop_finished(&mut self, finished: bool) {
+        self.map.insert(CONTEXT_LOOP_FINISHED.to_string(), finished.to_string());
+    }
+
+    pub fn set_dynamic_para(&mut self, template: &str) -> Result<()> {
+        self.set(CONTEXT_DYNAMIC_PARA, template)
+    }
+}
+
+impl BizObject {
+    /// typed view of `sys_context`
+    pub fn sys(&self) -> SysContext<'_> {
+        SysContext::new(&self.sys_context)
+    }
+
+    pub fn sys_mut(&mut self) -> SysContextMut<'_> {
+        SysContextMut::new(&mut self.sys_context)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn typed_test() {
+        let mut data = BizObject::default();
+        data.sys_mut().set_target_id(26);
+        data.sys_mut().set_target_para("a/b").unwrap();
+        data.sys_mut().set_loop_context(&LoopContext { next: "p2".to_string(), len: 3 });
+        data.sys_mut().set_loop_finished(true);
+        assert_eq!(data.sys_context[CONTEXT_TARGET_INSTANCE_ID], "1a");
+        let sys = data.sys();
+        assert_eq!(sys.target_id(), Ok(Some(26)));
+        assert_eq!(sys.target_para(), Some("a/b"));
+        assert_eq!(sys.loop_context(), Ok(Some(LoopContext { next: "p2".to_string(), len: 3 })));
+        assert_eq!(sys.loop_finished(), Ok(true));
+        assert_eq!(sys.loop_id(), None);
+        assert_eq!(sys.dynamic_para(), None);
+        assert_eq!(sys.verify(), Ok(()));
+        assert_eq!(BizObject::default().sys().loop_context(), Ok(None));
+        assert_eq!(BizObject::default().sys().loop_finished(), Ok(false));
+    }
+
+    #[test]
+    fn illegal_test() {
+        let mut data = BizObject::default();
+        let rtn = data.sys_mut().set_target_para("a|b");
+        assert_eq!(rtn, Err(NatureError::VerifyError("illegal value [a|b] for sys_context [target.para]: para can't contain [|]".to_string())));
+        assert_eq!(data.sys_mut().set(CONTEXT_LOOP_FINISHED, "yes").is_err(), true);
+        assert_eq!(data.sys_mut().set_loop_id("").is_err(), true);
+        // typo
+        assert_eq!(data.sys_mut().set("loop.nxt", "a"), Err(NatureError::VerifyError("unknown sys_context key: loop.nxt".to_string())));
+        assert_eq!(data.sys_context.is_empty(), true);
+        assert_eq!(data.sys_mut().set("para.x", "a").is_err(), true);
+        assert_eq!(data.sys_mut().set("target", "a"), Ok(()));
+        data.sys_context.clear();
+
+        data.sys_context.insert(CONTEXT_TARGET_INSTANCE_ID.to_string(), "xyz".to_string());
+        assert_eq!(data.sys().target_id().is_err(), true);
+        assert_eq!(data.sys().verify().is_err(), true);
+        data.sys_context.clear();
+        data.sys_context.insert(CONTEXT_LOOP_LEN.to_string(), "-1".to_string());
+        assert_eq!(data.sys().loop_context().is_err(), true);
+    }
+
+    #[test]
+    fn custom_key_test() {
+        fn upper(value: &str) -> Result<()> {
+            match value.chars().all(|c| c.is_ascii_uppercase()) {
+                true => Ok(()),
+                false => Err(NatureError::VerifyError("should be upper case".to_string()))
+            }
+        }
+        assert_eq!(register_sys_context_key("my.code", upper), Ok(()));
+        assert_eq!(register_sys_context_key(CONTEXT_LOOP_ID, upper).is_err(), true);
+        let mut data = BizObject::default();
+        assert_eq!(data.sys_mut().set("my.code", "AB"), Ok(()));
+        assert_eq!(data.sys_mut().set("my.code", "ab").is_err(), true);
+        assert_eq!(data.sys().get::<String>("my.code"), Ok(Some("AB".to_string())));
+        assert_eq!(data.sys().verify(), Ok(()));
+    }
+}
@@ -0,0 +1,34 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// (status, body, delay before responding)
+pub type Response = (u16, &'static str, Duration);
+
+/// responses with empty body and no delay
+pub fn statuses(statuses: &[u16]) -> Vec<Response> {
+    statuses.iter().map(|status| (*status, "", Duration::from_millis(0))).collect()
+}
+
+/// A stand-in http server which serves one request for each response, it returns the url for `path`,
+/// and the received requests are recorded.
+pub fn serve(path: &str, responses: Vec<Response>) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}{}", listener.local_addr().unwrap(), path);
+    let received = Arc::new(Mutex::new(vec![]));
+    let rtn = received.clone();
+    thread::spawn(move || {
+        for (status, body, wait) in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 4096];
+            let len = stream.read(&mut buf).unwrap_or(0);
+            received.lock().unwrap().push(String::from_utf8_lossy(&buf[..len]).to_string());
+            thread::sleep(wait);
+            let rtn = format!("HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body);
+            let _ = stream.write_all(rtn.as_bytes());
+        }
+    });
+    (url, rtn)
+}
@@ -1,8 +1,10 @@
+pub use generator::*;
 #[cfg(feature = "id128")]
 pub use id128::*;
 #[cfg(feature = "id64")]
 pub use id64::*;
 
+mod generator;
 #[cfg(feature = "id128")]
 mod id128;
 #[cfg(feature = "id64")]
@@ -0,0 +1,349 @@
+use std::env;
+use std::hash::Hasher;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use chrono::prelude::*;
+#[cfg(feature = "id128")]
+use siphasher::sip128::Hasher128;
+#[cfg(feature = "id64")]
+use siphasher::sip::SipHasher24;
+#[cfg(feature = "id128")]
+use uuid::Uuid;
+
+use crate::{BizObject, generate_id, ID, NatureError, Result, WORKER_ID};
+
+/// Generate id for the `Instance` which has no para.
+pub trait IdGenerator: Send + Sync {
+    fn generate(&self, data: &BizObject) -> Result<ID>;
+}
+
+/// The default one is stable across versions and platforms.
+/// The ids of the instances without para saved by former versions are generated by "legacy",
+/// set `ID_GENERATOR` to "legacy" to find them until they are migrated.
+pub static DEFAULT_ID_GENERATOR: &str = "siphash";
+
+lazy_static! {
+    static ref ID_GENERATOR: RwLock<Result<Arc<dyn IdGenerator>>> = {
+        let name = env::var("ID_GENERATOR").unwrap_or_else(|_| DEFAULT_ID_GENERATOR.to_string());
+        RwLock::new(init_id_generator(&name))
+    };
+    static ref SNOWFLAKE: Result<Arc<SnowflakeGenerator>> = {
+        let rtn = worker_id(&WORKER_ID).and_then(SnowflakeGenerator::new).map(Arc::new);
+        if let Err(e) = &rtn {
+            warn!("time-ordered id is unavailable: {}", e);
+        }
+        rtn
+    };
+}
+
+/// The generator used by `Instance::revise`, it can be appointed by the `ID_GENERATOR` environment variable
+/// or by `set_id_generator`, the default is `DEFAULT_ID_GENERATOR`.
+/// An unknown `ID_GENERATOR` is an error until another generator is set.
+pub fn id_generator() -> Result<Arc<dyn IdGenerator>> {
+    ID_GENERATOR.read().unwrap().clone()
+}
+
+pub fn set_id_generator(generator: Arc<dyn IdGenerator>) {
+    *ID_GENERATOR.write().unwrap() = Ok(generator);
+}
+
+/// The time-ordered generator shared in this process, its worker id comes from `WORKER_ID`.
+/// An illegal `WORKER_ID` is checked only once, and the error is returned for each call.
+pub fn snowflake_generator() -> Result<Arc<SnowflakeGenerator>> {
+    SNOWFLAKE.clone()
+}
+
+fn worker_id(value: &str) -> Result<u32> {
+    match value.trim().parse::<u32>() {
+        Ok(id) => Ok(id),
+        Err(e) => Err(NatureError::VerifyError(format!("WORKER_ID should be a number, but get [{}]: {}", value, e)))
+    }
+}
+
+/// an unknown name is checked only once, it should not panic in `Instance::revise`
+fn init_id_generator(name: &str) -> Result<Arc<dyn IdGenerator>> {
+    let rtn = id_generator_by_name(name);
+    if let Err(e) = &rtn {
+        warn!("ID_GENERATOR is unavailable: {}", e);
+    }
+    rtn
+}
+
+/// names: siphash, fnv, uuid5(id128 only), snowflake, legacy.
+/// snowflake is the one returned by `snowflake_generator`.
+pub fn id_generator_by_name(name: &str) -> Result<Arc<dyn IdGenerator>> {
+    match name {
+        "siphash" => Ok(Arc::new(SipHashGenerator::default())),
+        "fnv" => Ok(Arc::new(FnvGenerator)),
+        #[cfg(feature = "id128")]
+        "uuid5" => Ok(Arc::new(Uuid5Generator)),
+        "snowflake" => Ok(snowflake_generator()?),
+        "legacy" => Ok(Arc::new(LegacyGenerator)),
+        _ => Err(NatureError::VerifyError(format!("unknown id generator: {}", name)))
+    }
+}
+
+/// The bytes to be hashed, it does not depend on the `Hash` implementation of std.
+/// maps and sets are sorted, each string is prefixed with its length.
+pub fn id_input(data: &BizObject) -> Vec<u8> {
+    fn put_str(buf: &mut Vec<u8>, s: &str) {
+        buf.extend_from_slice(&(s.len() as u64).to_le_bytes());
+        buf.extend_from_slice(s.as_bytes());
+    }
+    let mut buf: Vec<u8> = vec![];
+    put_str(&mut buf, &data.meta);
+    put_str(&mut buf, &data.content);
+    buf.extend_from_slice(&data.state_version.to_le_bytes());
+    match &data.from {
+        None => buf.push(0),
+        Some(from) => {
+            buf.push(1);
+            buf.extend_from_slice(&from.id.to_le_bytes());
+            put_str(&mut buf, &from.meta);
+            put_str(&mut buf, &from.para);
+            buf.extend_from_slice(&from.state_version.to_le_bytes());
+        }
+    }
+    put_str(&mut buf, &data.para);
+    for map in &[&data.context, &data.sys_context] {
+        let mut entries: Vec<(&String, &String)> = map.iter().collect();
+        entries.sort();
+        buf.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        entries.iter().for_each(|(k, v)| {
+            put_str(&mut buf, k);
+            put_str(&mut buf, v);
+        });
+    }
+    let mut states: Vec<&String> = data.states.iter().collect();
+    states.sort();
+    buf.extend_from_slice(&(states.len() as u64).to_le_bytes());
+    states.iter().for_each(|one| put_str(&mut buf, one));
+    buf
+}
+
+/// SipHash-2-4 with fixed keys
+#[derive(Debug, Clone, Default)]
+pub struct SipHashGenerator {
+    pub key0: u64,
+    pub key1: u64,
+}
+
+impl IdGenerator for SipHashGenerator {
+    #[cfg(feature = "id64")]
+    fn generate(&self, data: &BizObject) -> Result<ID> {
+        let mut hasher = SipHasher24::new_with_keys(self.key0, self.key1);
+        hasher.write(&id_input(data));
+        Ok(hasher.finish())
+    }
+
+    #[cfg(feature = "id128")]
+    fn generate(&self, data: &BizObject) -> Result<ID> {
+        let mut hasher = siphasher::sip128::SipHasher24::new_with_keys(self.key0, self.key1);
+        hasher.write(&id_input(data));
+        let h = hasher.finish128();
+        Ok(h.h1 as u128 | (h.h2 as u128) << 64)
+    }
+}
+
+/// FNV-1a
+#[derive(Debug, Clone, Default)]
+pub struct FnvGenerator;
+
+#[cfg(feature = "id64")]
+const FNV_OFFSET: ID = 0xcbf2_9ce4_8422_2325;
+#[cfg(feature = "id64")]
+const FNV_PRIME: ID = 0x0000_0100_0000_01b3;
+#[cfg(feature = "id128")]
+const FNV_OFFSET: ID = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+#[cfg(feature = "id128")]
+const FNV_PRIME: ID = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+
+impl IdGenerator for FnvGenerator {
+    fn generate(&self, data: &BizObject) -> Result<ID> {
+        let rtn = id_input(data).iter().fold(FNV_OFFSET, |hash, byte| {
+            (hash ^ *byte as ID).wrapping_mul(FNV_PRIME)
+        });
+        Ok(rtn)
+    }
+}
+
+/// UUID version 5 with the OID namespace
+#[cfg(feature = "id128")]
+#[derive(Debug, Clone, Default)]
+pub struct Uuid5Generator;
+
+#[cfg(feature = "id128")]
+impl IdGenerator for Uuid5Generator {
+    fn generate(&self, data: &BizObject) -> Result<ID> {
+        let uuid = Uuid::new_v5(&Uuid::NAMESPACE_OID, &id_input(data));
+        Ok(u128::from_be_bytes(*uuid.as_bytes()))
+    }
+}
+
+/// The ids of former versions, `DefaultHasher` for id64 and UUIDv3 over json for id128.
+/// They are not stable across builds, only for compatible use.
+#[derive(Debug, Clone, Default)]
+pub struct LegacyGenerator;
+
+impl IdGenerator for LegacyGenerator {
+    fn generate(&self, data: &BizObject) -> Result<ID> {
+        generate_id(data)
+    }
+}
+
+/// Time-ordered id, which does not depend on the content, the layout from high bits to low bits:
+/// - id64  : 41 bits milliseconds since 2020-01-01, 10 bits worker, 12 bits sequence
+/// - id128 : 64 bits milliseconds since 1970-01-01, 32 bits worker, 32 bits sequence
+pub struct SnowflakeGenerator {
+    worker: u32,
+    /// last milliseconds and sequence
+    last: Mutex<(i64, u32)>,
+}
+
+#[cfg(feature = "id64")]
+const SNOWFLAKE_EPOCH: i64 = 1_577_836_800_000;
+#[cfg(feature = "id64")]
+const SNOWFLAKE_WORKER_BITS: u32 = 10;
+#[cfg(feature = "id64")]
+const SNOWFLAKE_SEQ_BITS: u32 = 12;
+
+#[cfg(feature = "id128")]
+const SNOWFLAKE_EPOCH: i64 = 0;
+#[cfg(feature = "id128")]
+const SNOWFLAKE_WORKER_BITS: u32 = 32;
+#[cfg(feature = "id128")]
+const SNOWFLAKE_SEQ_BITS: u32 = 32;
+
+impl SnowflakeGenerator {
+    pub fn new(worker: u32) -> Result<Self> {
+        if u64::from(worker) >= 1u64 << SNOWFLAKE_WORKER_BITS {
+            let msg = format!("worker id should be less than {}, but get {}", 1u64 << SNOWFLAKE_WORKER_BITS, worker);
+            return Err(NatureError::VerifyError(msg));
+        }
+        Ok(SnowflakeGenerator { worker, last: Mutex::new((0, 0)) })
+    }
+
+    pub fn worker(&self) -> u32 {
+        self.worker
+    }
+
+    pub fn next_id(&self) -> ID {
+        let max_seq = ((1u64 << SNOWFLAKE_SEQ_BITS) - 1) as u32;
+        let mut last = self.last.lock().unwrap();
+        let mut now = Local::now().timestamp_millis() - SNOWFLAKE_EPOCH;
+        loop {
+            // the clock moved backwards, keep using the last time.
+            if now < last.0 {
+                now = last.0;
+            }
+            if now > last.0 {
+                *last = (now, 0);
+                break;
+            }
+            if last.1 < max_seq {
+                last.1 += 1;
+                break;
+            }
+            // sequence exhausted in this millisecond
+            thread::sleep(Duration::from_micros(100));
+            now = Local::now().timestamp_millis() - SNOWFLAKE_EPOCH;
+        }
+        (last.0 as ID) << (SNOWFLAKE_WORKER_BITS + SNOWFLAKE_SEQ_BITS)
+            | (self.worker as ID) << SNOWFLAKE_SEQ_BITS
+            | last.1 as ID
+    }
+}
+
+impl IdGenerator for SnowflakeGenerator {
+    fn generate(&self, _data: &BizObject) -> Result<ID> {
+        Ok(self.next_id())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Instance;
+
+    use super::*;
+
+    fn data() -> BizObject {
+        let mut ins = Instance::new("sale/order").unwrap();
+        ins.content = "hello".to_string();
+        ins.context.insert("b".to_string(), "2".to_string());
+        ins.context.insert("a".to_string(), "1".to_string());
+        ins.states.insert("new".to_string());
+        ins.data.clone()
+    }
+
+    #[test]
+    fn input_test() {
+        let mut other = data();
+        // insert in different order
+        other.context.clear();
+        other.context.insert("a".to_string(), "1".to_string());
+        other.context.insert("b".to_string(), "2".to_string());
+        assert_eq!(id_input(&data()), id_input(&other));
+        other.content = "world".to_string();
+        assert_ne!(id_input(&data()), id_input(&other));
+    }
+
+    #[test]
+    #[cfg(feature = "id64")]
+    fn stable_test() {
+        // these values must never change
+        assert_eq!(SipHashGenerator::default().generate(&data()).unwrap(), 0x4a6b_2067_b35d_76dc);
+        assert_eq!(FnvGenerator.generate(&data()).unwrap(), 0x5d9c_d3ba_cf7f_9995);
+    }
+
+    #[test]
+    fn different_generator() {
+        let sip = SipHashGenerator::default().generate(&data()).unwrap();
+        let keyed = SipHashGenerator { key0: 1, key1: 2 }.generate(&data()).unwrap();
+        let fnv = FnvGenerator.generate(&data()).unwrap();
+        assert_ne!(sip, keyed);
+        assert_ne!(sip, fnv);
+        // the json of multi-entries map is unordered, so the legacy id128 is unstable for it.
+        let mut single = data();
+        single.context.remove("b");
+        assert_eq!(LegacyGenerator.generate(&single).unwrap(), generate_id(&single).unwrap());
+    }
+
+    #[test]
+    fn by_name_test() {
+        assert_eq!(id_generator_by_name("siphash").is_ok(), true);
+        assert_eq!(id_generator_by_name("fnv").is_ok(), true);
+        assert_eq!(id_generator_by_name("snowflake").is_ok(), true);
+        assert_eq!(id_generator_by_name("legacy").is_ok(), true);
+        assert_eq!(id_generator_by_name("uuid5").is_ok(), cfg!(feature = "id128"));
+        assert_eq!(id_generator_by_name("md5").is_err(), true);
+    }
+
+    #[test]
+    fn init_test() {
+        let mut single = data();
+        single.context.remove("b");
+        let legacy = generate_id(&single).unwrap();
+        assert_eq!(init_id_generator("bogus").is_err(), true);
+        assert_eq!(init_id_generator(DEFAULT_ID_GENERATOR).unwrap().generate(&single).unwrap(), SipHashGenerator::default().generate(&single).unwrap());
+        assert_eq!(init_id_generator("legacy").unwrap().generate(&single).unwrap(), legacy);
+        assert_eq!(init_id_generator("fnv").unwrap().generate(&single).unwrap(), FnvGenerator.generate(&single).unwrap());
+    }
+
+    #[test]
+    fn snowflake_test() {
+        let generator = SnowflakeGenerator::new(3).unwrap();
+        let mut last: ID = 0;
+        for _ in 0..10000 {
+            let id = generator.generate(&data()).unwrap();
+            assert_eq!(id > last, true);
+            assert_eq!((id >> SNOWFLAKE_SEQ_BITS) & ((1 << SNOWFLAKE_WORKER_BITS) - 1), 3);
+            last = id;
+        }
+        assert_eq!(SnowflakeGenerator::new(1 << 10).is_ok(), cfg!(feature = "id128"));
+        assert_eq!(worker_id(" 7"), Ok(7));
+        assert_eq!(worker_id("a").is_err(), true);
+        assert_eq!(worker_id("-1").is_err(), true);
+    }
+}